@@ -0,0 +1,128 @@
+// Exact arithmetic on HoloFuel amounts. `Fuel` can only be added and compared, so for anything else
+// amounts are converted to an integer number of 10^-FUEL_SCALE HF units. Every operation is checked,
+// amounts that do not fit are reported as errors instead of overflowing.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use holofuel_types::fuel::Fuel;
+
+/// Number of decimal places of fuel units
+pub const FUEL_SCALE: u32 = 24;
+
+/// Number of decimal places results of division are rounded to
+pub const DIVISION_SCALE: u32 = 6;
+
+/// Fuel amount as an integer number of 10^-FUEL_SCALE units, so that amounts can be compared exactly
+pub fn to_units(fuel: Fuel) -> Result<i128> {
+    let fuel = fuel.to_string();
+    let (sign, abs) = match fuel.strip_prefix('-') {
+        Some(abs) => (-1, abs),
+        None => (1, fuel.as_str()),
+    };
+    let (integer, fraction) = abs.split_once('.').unwrap_or((abs, ""));
+    if fraction.len() > FUEL_SCALE as usize {
+        return Err(anyhow!("Fuel amount {} is too precise", fuel));
+    }
+    let units: i128 = format!(
+        "{}{:0<width$}",
+        integer,
+        fraction,
+        width = FUEL_SCALE as usize
+    )
+    .parse()
+    .map_err(|e| anyhow!("Fuel amount {} is too large: {}", fuel, e))?;
+    Ok(sign * units)
+}
+
+/// Fuel amount of `units` of 10^-FUEL_SCALE HF
+pub fn from_units(units: i128) -> Result<Fuel> {
+    let sign = if units < 0 { "-" } else { "" };
+    let abs = units.unsigned_abs();
+    let one = 10_u128.pow(FUEL_SCALE);
+    let fraction = format!("{:0>width$}", abs % one, width = FUEL_SCALE as usize);
+    let fraction = fraction.trim_end_matches('0');
+
+    let fuel = if fraction.is_empty() {
+        format!("{}{}", sign, abs / one)
+    } else {
+        format!("{}{}.{}", sign, abs / one, fraction)
+    };
+    Fuel::from_str(&fuel).map_err(|e| anyhow!("Failed to convert {} to fuel: {:?}", fuel, e))
+}
+
+/// Units of `fuel` multiplied by `factor`
+pub fn multiply_units(fuel: Fuel, factor: u32) -> Result<i128> {
+    to_units(fuel)?
+        .checked_mul(i128::from(factor))
+        .ok_or_else(|| anyhow!("Fuel amount {} times {} is too large", fuel, factor))
+}
+
+/// Divides `fuel` by `divisor`, rounded half away from zero to DIVISION_SCALE decimal places
+pub fn divide(fuel: Fuel, divisor: u64) -> Result<Fuel> {
+    if divisor == 0 {
+        return Err(anyhow!("Can not divide fuel by 0"));
+    }
+
+    // quotient is computed in units of 10^-DIVISION_SCALE HF
+    let step = 10_i128.pow(FUEL_SCALE - DIVISION_SCALE);
+    let divisor = i128::from(divisor)
+        .checked_mul(step)
+        .ok_or_else(|| anyhow!("Can not divide fuel by {}", divisor))?;
+    let units = to_units(fuel)?;
+
+    let mut quotient = units / divisor;
+    let remainder = units % divisor;
+    if remainder.unsigned_abs() >= divisor.unsigned_abs().div_ceil(2) {
+        quotient += units.signum();
+    }
+
+    from_units(quotient * step)
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use holofuel_types::fuel::Fuel;
+
+    use super::{divide, from_units, multiply_units, to_units};
+
+    fn fuel(s: &str) -> Fuel {
+        Fuel::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn converts_fuel_to_units() {
+        assert_eq!(to_units(fuel("1")).unwrap(), 10_i128.pow(24));
+        assert_eq!(to_units(fuel("0.5")).unwrap(), 5 * 10_i128.pow(23));
+        assert!(to_units(fuel("-1")).unwrap() < 0);
+
+        assert_eq!(from_units(10_i128.pow(24)).unwrap(), fuel("1"));
+        assert_eq!(from_units(-25 * 10_i128.pow(22)).unwrap(), fuel("-0.25"));
+    }
+
+    #[test]
+    fn multiplies_fuel_without_overflow() {
+        assert_eq!(
+            multiply_units(fuel("2"), 50).unwrap(),
+            100 * 10_i128.pow(24)
+        );
+        // i128 holds about 1.7 * 10^14 HF
+        assert!(multiply_units(fuel("100000000000000"), 100).is_err());
+    }
+
+    #[test]
+    fn divides_fuel() {
+        assert_eq!(divide(fuel("100"), 4).unwrap(), fuel("25"));
+        assert_eq!(divide(fuel("10.4"), 2).unwrap(), fuel("5.2"));
+        assert_eq!(divide(fuel("0.3"), 3).unwrap(), fuel("0.1"));
+        assert_eq!(divide(fuel("-9"), 3).unwrap(), fuel("-3"));
+        // quotients of integer amounts have fractions
+        assert_eq!(divide(fuel("1"), 2).unwrap(), fuel("0.5"));
+        assert_eq!(divide(fuel("100"), 3).unwrap(), fuel("33.333333"));
+        assert_eq!(divide(fuel("2"), 3).unwrap(), fuel("0.666667"));
+        assert_eq!(divide(fuel("-2"), 3).unwrap(), fuel("-0.666667"));
+        assert!(divide(fuel("1"), 0).is_err());
+    }
+}
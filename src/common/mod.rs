@@ -1,4 +1,7 @@
 pub mod consts;
+pub mod fuel;
 pub mod hbs;
 pub mod keypair;
+pub mod series;
+pub mod store;
 pub mod types;
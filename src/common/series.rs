@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, TimeDelta, Timelike, Utc};
+use holochain_types::prelude::Timestamp;
+use rocket::{
+    serde::{Deserialize, Serialize},
+    FromFormField,
+};
+
+/// Width of a single point of a time series
+#[derive(Serialize, Deserialize, FromFormField, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub enum Bucket {
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+impl Bucket {
+    /// Returns start of the bucket that `time` falls into. Weeks start on Monday.
+    pub fn start_of(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let date = time.date_naive();
+        let start = match self {
+            Bucket::Hour => {
+                return date
+                    .and_hms_opt(time.hour(), 0, 0)
+                    .unwrap_or_default()
+                    .and_utc()
+            }
+            Bucket::Day => date,
+            Bucket::Week => date - Days::new(date.weekday().num_days_from_monday().into()),
+            Bucket::Month => date.with_day(1).unwrap_or(date),
+        };
+        start_of_day(start)
+    }
+
+    /// Returns start of the bucket that follows the one starting at `start`
+    pub fn next(&self, start: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Bucket::Hour => start + TimeDelta::hours(1),
+            Bucket::Day => start + Days::new(1),
+            Bucket::Week => start + Days::new(7),
+            Bucket::Month => start + Months::new(1),
+        }
+    }
+}
+
+/// Max number of buckets in a series, a bit over a year of hours
+pub const MAX_BUCKETS: usize = 10_000;
+
+/// Buckets of a time series covering [`from`, `to`)
+pub struct Buckets {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub starts: Vec<DateTime<Utc>>,
+    bucket: Bucket,
+    index: HashMap<DateTime<Utc>, usize>, // position of each start in `starts`
+}
+
+impl Buckets {
+    /// Lists all buckets overlapping with [`from`, `to`), fails when there are more than MAX_BUCKETS of them
+    pub fn new(from: DateTime<Utc>, to: DateTime<Utc>, bucket: Bucket) -> Result<Self> {
+        let mut starts = vec![];
+        let mut start = bucket.start_of(from);
+        while start < to {
+            if starts.len() == MAX_BUCKETS {
+                return Err(anyhow!(
+                    "Range has more than {} buckets, use a shorter range or a wider bucket",
+                    MAX_BUCKETS
+                ));
+            }
+            starts.push(start);
+            start = bucket.next(start);
+        }
+
+        let index = starts
+            .iter()
+            .enumerate()
+            .map(|(i, start)| (*start, i))
+            .collect();
+        Ok(Buckets {
+            from,
+            to,
+            starts,
+            bucket,
+            index,
+        })
+    }
+
+    /// Returns position of the bucket that `time` falls into, None when `time` is out of range
+    pub fn index_of(&self, time: DateTime<Utc>) -> Option<usize> {
+        if time < self.from || time >= self.to {
+            return None;
+        }
+        self.index.get(&self.bucket.start_of(time)).copied()
+    }
+}
+
+/// Parses time range from query params. Both `from` and `to` can be either a date (`2024-07-01`)
+/// or an RFC 3339 date-time (`2024-07-01T12:00:00Z`). A date in `to` includes the whole day.
+/// When `from` is omitted the range starts `default_days` before `to`, when `to` is omitted it ends now.
+pub fn parse_range(
+    from: Option<&str>,
+    to: Option<&str>,
+    default_days: u64,
+) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+    let to = match to {
+        Some(to) => parse_bound(to, true)?,
+        None => Utc::now(),
+    };
+
+    let from = match from {
+        Some(from) => parse_bound(from, false)?,
+        None => to
+            .checked_sub_days(Days::new(default_days))
+            .ok_or_else(|| anyhow!("Range of {} days is too long", default_days))?,
+    };
+
+    if from >= to {
+        return Err(anyhow!("`from` has to be earlier than `to`"));
+    }
+
+    Ok((from, to))
+}
+
+pub fn timestamp_to_date_time(timestamp: Timestamp) -> DateTime<Utc> {
+    DateTime::from_timestamp_micros(timestamp.as_micros()).unwrap_or_default()
+}
+
+/// Parses a date or an RFC 3339 date-time as a bound of a range. A date as an `end` bound
+/// is the start of the following day, so that the range includes the whole day.
+pub fn parse_bound(s: &str, end: bool) -> Result<DateTime<Utc>> {
+    match NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        Ok(date) if end => Ok(start_of_day(date) + Days::new(1)),
+        Ok(date) => Ok(start_of_day(date)),
+        Err(_) => parse_date_time(s),
+    }
+}
+
+fn parse_date_time(s: &str) -> Result<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(s)
+        .map_err(|e| anyhow!("Failed to parse {} as a date or date-time: {}", s, e))?
+        .with_timezone(&Utc))
+}
+
+fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc()
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, Utc};
+
+    use super::{parse_range, Bucket, Buckets};
+
+    fn time(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn finds_bucket_start() {
+        // 2024-07-03 is a Wednesday
+        let t = time("2024-07-03T15:20:00Z");
+        assert_eq!(Bucket::Hour.start_of(t), time("2024-07-03T15:00:00Z"));
+        assert_eq!(Bucket::Day.start_of(t), time("2024-07-03T00:00:00Z"));
+        assert_eq!(Bucket::Week.start_of(t), time("2024-07-01T00:00:00Z"));
+        assert_eq!(Bucket::Month.start_of(t), time("2024-07-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn lists_bucket_starts_in_range() {
+        let (from, to) = parse_range(Some("2024-01-30"), Some("2024-03-01"), 30).unwrap();
+        let buckets = Buckets::new(from, to, Bucket::Month).unwrap();
+        assert_eq!(
+            buckets.starts,
+            vec![
+                time("2024-01-01T00:00:00Z"),
+                time("2024-02-01T00:00:00Z"),
+                time("2024-03-01T00:00:00Z"),
+            ]
+        );
+        assert_eq!(buckets.index_of(time("2024-02-29T23:59:59Z")), Some(1));
+        assert_eq!(buckets.index_of(time("2024-01-29T00:00:00Z")), None);
+        assert_eq!(buckets.index_of(time("2024-03-02T00:00:00Z")), None);
+
+        let buckets = Buckets::new(from, to, Bucket::Day).unwrap();
+        assert_eq!(buckets.starts.len(), 32);
+    }
+
+    #[test]
+    fn rejects_too_many_buckets() {
+        let (from, to) = parse_range(Some("1970-01-01"), Some("2024-01-01"), 30).unwrap();
+        assert!(Buckets::new(from, to, Bucket::Hour).is_err());
+        assert!(Buckets::new(from, to, Bucket::Month).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_range() {
+        assert!(parse_range(Some("2024-03-01"), Some("2024-01-01"), 7).is_err());
+        assert!(parse_range(Some("yesterday"), None, 7).is_err());
+        assert!(parse_range(None, None, 100_000_000).is_err());
+    }
+}
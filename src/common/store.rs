@@ -0,0 +1,51 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use rocket::serde::{de::DeserializeOwned, json::serde_json, Serialize};
+
+/// Reads JSON file at `path`, returns default value when the file does not exist yet
+pub fn load_json<T: DeserializeOwned + Default>(path: &Path) -> Result<T> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let bytes = fs::read(path).with_context(|| format!("failed to read {:?}", path))?;
+    serde_json::from_slice(&bytes).with_context(|| format!("failed to parse {:?}", path))
+}
+
+/// Writes `value` as JSON to `path`. File is replaced in one step,
+/// so that a crash in the middle of writing does not leave it corrupted.
+pub fn save_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, serde_json::to_vec_pretty(value)?)
+        .with_context(|| format!("failed to write {:?}", tmp_path))?;
+    fs::rename(&tmp_path, path).with_context(|| format!("failed to write {:?}", path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::env;
+
+    use super::{load_json, save_json};
+
+    #[test]
+    fn saves_and_loads_json() {
+        let path = env::temp_dir()
+            .join("hpos-api-rust-test-store")
+            .join("store.json");
+        let _ = std::fs::remove_file(&path);
+
+        let empty: HashMap<String, u32> = load_json(&path).unwrap();
+        assert!(empty.is_empty());
+
+        let value = HashMap::from([("a".to_string(), 1)]);
+        save_json(&path, &value).unwrap();
+        let loaded: HashMap<String, u32> = load_json(&path).unwrap();
+        assert_eq!(loaded, value);
+    }
+}
@@ -0,0 +1,191 @@
+// Hosted web agents of a happ. Each web user's agent gets its own instance of the hosted happ
+// installed under `<happ_id>:<agent_pubkey>`, so each instance holds a source chain of a single agent.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use holochain_client::{AgentPubKey, AppInfo};
+use holochain_conductor_api::AppInfoStatus;
+use holochain_types::{
+    dna::{ActionHashB64, AgentPubKeyB64},
+    prelude::{ActionType, ChainQueryFilter, Timestamp},
+};
+use log::{debug, warn};
+use rocket::serde::{Deserialize, Serialize};
+
+use super::hosted_happs::{query_service_logs, to_log_entry, LogEntry};
+use super::install::{handle_holochain_disable, handle_holochain_uninstall};
+use crate::hpos::Ws;
+
+/// Lists all agent instances of happ `id` installed on this holoport
+pub async fn handle_get_hosted_agents(ws: &mut Ws, id: ActionHashB64) -> Result<Vec<HostedAgent>> {
+    let instances = list_agent_instances(ws, &id).await?;
+
+    // Servicelogger may not be installed yet or may be unreachable, agents are listed regardless
+    let activity = match get_agents_activity(ws, &id).await {
+        Ok(activity) => activity,
+        Err(e) => {
+            warn!("failed to read servicelogger of happ {}: {}", &id, e);
+            AgentsActivity::default()
+        }
+    };
+
+    Ok(instances
+        .into_iter()
+        .map(|info| {
+            let agent = AgentPubKeyB64::from(info.agent_pub_key.clone());
+            HostedAgent {
+                last_activity: activity.last_request.get(&info.agent_pub_key).copied(),
+                average_storage: activity.storage_per_source_chain,
+                agent,
+                installed_app_id: info.installed_app_id,
+                status: info.status,
+                installed_at: info.installed_at,
+            }
+        })
+        .collect())
+}
+
+/// Disables instance of happ `id` that holds source chain of `agent`.
+/// Source chain is preserved, instance can be enabled again by the next request of the agent.
+/// Returns id of the disabled instance, or None when `agent` is not hosted for the happ.
+pub async fn handle_pause_agent(
+    ws: &mut Ws,
+    id: ActionHashB64,
+    agent: AgentPubKeyB64,
+) -> Result<Option<String>> {
+    let Some(installed_app_id) = find_agent_instance(ws, &id, &agent).await? else {
+        return Ok(None);
+    };
+
+    debug!("disabling agent instance {}", &installed_app_id);
+    handle_holochain_disable(&mut ws.admin, &installed_app_id).await?;
+    ws.apps.remove(&installed_app_id);
+
+    Ok(Some(installed_app_id))
+}
+
+/// Uninstalls instance of happ `id` that holds source chain of `agent`.
+/// Returns id of the uninstalled instance, or None when `agent` is not hosted for the happ.
+pub async fn handle_uninstall_agent(
+    ws: &mut Ws,
+    id: ActionHashB64,
+    agent: AgentPubKeyB64,
+) -> Result<Option<String>> {
+    let Some(installed_app_id) = find_agent_instance(ws, &id, &agent).await? else {
+        return Ok(None);
+    };
+
+    debug!("uninstalling agent instance {}", &installed_app_id);
+    handle_holochain_uninstall(&mut ws.admin, &installed_app_id).await?;
+    ws.apps.remove(&installed_app_id);
+
+    Ok(Some(installed_app_id))
+}
+
+async fn list_agent_instances(ws: &mut Ws, id: &ActionHashB64) -> Result<Vec<AppInfo>> {
+    Ok(ws
+        .admin
+        .list_apps(None)
+        .await
+        .map_err(|err| anyhow!("{:?}", err))?
+        .into_iter()
+        .filter(|info| is_agent_instance(&info.installed_app_id, id))
+        .collect())
+}
+
+async fn find_agent_instance(
+    ws: &mut Ws,
+    id: &ActionHashB64,
+    agent: &AgentPubKeyB64,
+) -> Result<Option<String>> {
+    let installed_app_id = format!("{}:{}", id, agent);
+
+    Ok(list_agent_instances(ws, id)
+        .await?
+        .into_iter()
+        .find(|info| info.installed_app_id == installed_app_id)
+        .map(|info| info.installed_app_id))
+}
+
+/// Checks if `installed_app_id` follows naming convention of web agent's instance of happ `happ_id`
+fn is_agent_instance(installed_app_id: &str, happ_id: &ActionHashB64) -> bool {
+    installed_app_id.starts_with(&format!("{}:uhCA", happ_id))
+}
+
+#[derive(Default)]
+struct AgentsActivity {
+    last_request: HashMap<AgentPubKey, Timestamp>,
+    storage_per_source_chain: Option<u64>,
+}
+
+/// Reads the last request of each agent from activity logs of happ's servicelogger.
+/// Disk usage logs report storage of all the happ's DNAs together, so the storage of a single agent
+/// is estimated as an even share of the latest snapshot among source chains counted in it.
+async fn get_agents_activity(ws: &mut Ws, id: &ActionHashB64) -> Result<AgentsActivity> {
+    let filter = ChainQueryFilter::new()
+        .include_entries(true)
+        .action_type(ActionType::Create);
+    let records = query_service_logs(ws, id, filter).await?;
+
+    let mut activity = AgentsActivity::default();
+    for record in records.iter() {
+        match to_log_entry(record) {
+            Some(LogEntry::ActivityLog(log)) => {
+                let timestamp = log.request.request.timestamp;
+                activity
+                    .last_request
+                    .entry(log.request.agent_id)
+                    .and_modify(|last| *last = (*last).max(timestamp))
+                    .or_insert(timestamp);
+            }
+            Some(LogEntry::DiskUsageLog(log)) => {
+                let total: u64 = log.files.iter().map(|file| file.size).sum();
+                activity.storage_per_source_chain =
+                    Some(total / u64::from(log.source_chain_count.max(1)));
+            }
+            None => {}
+        }
+    }
+
+    Ok(activity)
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct HostedAgent {
+    pub agent: AgentPubKeyB64,
+    pub installed_app_id: String,
+    pub status: AppInfoStatus,
+    pub installed_at: Timestamp,
+    pub last_activity: Option<Timestamp>, // null when agent has made no request yet
+    pub average_storage: Option<u64>, // happ's storage per source chain, null when there is no disk usage log yet
+}
+
+#[cfg(test)]
+mod test {
+    use holochain_types::dna::ActionHashB64;
+
+    use super::is_agent_instance;
+
+    #[test]
+    fn matches_agent_instance_ids() {
+        let happ_id =
+            ActionHashB64::from_b64_str("uhCkklkJVx4u17eCaaKg_phRJsHOj9u57v_4cHQR-Bd9tb-vePRyC")
+                .unwrap();
+
+        assert!(is_agent_instance(
+            &format!(
+                "{}:uhCAkMdhGSO7W7ccCEd7UthPCiB37tNcO10MTEuBDIC5fS1MI2IsR",
+                happ_id
+            ),
+            &happ_id
+        ));
+        assert!(!is_agent_instance(&happ_id.to_string(), &happ_id));
+        assert!(!is_agent_instance(
+            &format!("{}::servicelogger", happ_id),
+            &happ_id
+        ));
+    }
+}
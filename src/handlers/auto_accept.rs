@@ -0,0 +1,413 @@
+/*
+Auto-accept Overview
+
+Host can let the API enable newly published happs on their behalf, according to a policy kept in
+`<HPOS_API_DATA_DIR>/auto_accept.json`:
+- prices: publisher pays at least `minPricePercent` % of host's default prices from billing preferences
+- categories: happ belongs to at least one of the listed categories
+- jurisdiction: happ's jurisdiction rules allow hosting in host's jurisdiction, as read from HBS
+- max hosted happs: happs are accepted only while fewer than `maxHostedHapps` happs are enabled
+
+Every HPOS_API_AUTO_ACCEPT_INTERVAL seconds (10 minutes by default, 0 turns it off) happs from `hha/get_happs`
+the host has not decided on are evaluated: published happs that are not enabled, that the host has not disabled
+and that have no decision since the policy was last changed. Matching happs are installed as install jobs
+with the same flow as `/hosted/install`. Every decision is recorded in `<HPOS_API_DATA_DIR>/auto_accept_log.json`.
+*/
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use holochain_types::prelude::Timestamp;
+use holofuel_types::fuel::Fuel;
+use hpos_hc_connect::{hha_agent::CoreAppAgent, hha_types::HappPreferences as HostPreferences};
+use log::{debug, info, warn};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio::{self, time};
+
+use super::hosted_happs::get_all_happs;
+use super::install::{self, InstallJobsMutex, JobProgress};
+use super::preflight::{check_jurisdiction, CheckStatus};
+use crate::common::fuel;
+use crate::common::hbs::HBS;
+use crate::common::store::{load_json, save_json};
+use crate::common::types::{PresentedHappBundle, PublisherPricingPref};
+use crate::hpos::{get_data_dir, WsMutex};
+
+const DEFAULT_INTERVAL: u64 = 600;
+
+/// Max number of decisions kept in the log, the oldest ones are dropped first
+const MAX_LOG_ENTRIES: usize = 1000;
+
+/// Evaluates undecided happs every HPOS_API_AUTO_ACCEPT_INTERVAL seconds in the background, while policy is enabled
+pub fn spawn_auto_accept(wsm: WsMutex, jobs: InstallJobsMutex) {
+    let interval = Duration::from_secs(
+        std::env::var("HPOS_API_AUTO_ACCEPT_INTERVAL")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_INTERVAL),
+    );
+    if interval.is_zero() {
+        info!("auto-accept of hosted happs is turned off");
+        return;
+    }
+
+    tokio::spawn(async move {
+        // HBS client managed by rocket is only reachable from requests, the task keeps its own
+        let hbs = HBS::new();
+        let mut ticker = time::interval_at(time::Instant::now() + interval, interval);
+        loop {
+            ticker.tick().await;
+            match load_policy() {
+                Ok(policy) if !policy.enabled => continue,
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("failed to read auto-accept policy: {}", e);
+                    continue;
+                }
+            }
+            if let Err(e) = handle_run_auto_accept(&wsm, &jobs, &mut *hbs.lock().await).await {
+                warn!("auto-accept of hosted happs failed: {}", e);
+            }
+        }
+    });
+}
+
+pub fn handle_get_policy() -> Result<AutoAcceptPolicy> {
+    load_policy()
+}
+
+/// Saves policy, happs decided on under the previous policy are evaluated again
+pub fn handle_set_policy(mut policy: AutoAcceptPolicy) -> Result<AutoAcceptPolicy> {
+    policy.updated_at = Some(Timestamp::now());
+    save_json(&get_data_dir().join("auto_accept.json"), &policy)?;
+    Ok(policy)
+}
+
+/// Returns decisions made so far, newest first
+pub fn handle_get_log() -> Result<Vec<AutoAcceptDecision>> {
+    let mut log = load_log()?;
+    log.decisions.reverse();
+    Ok(log.decisions)
+}
+
+/// Evaluates happs host has not decided on and starts installation of those matching the policy
+pub async fn handle_run_auto_accept(
+    wsm: &WsMutex,
+    jobs: &InstallJobsMutex,
+    hbs: &mut HBS,
+) -> Result<Vec<AutoAcceptDecision>> {
+    let policy = load_policy()?;
+    if !policy.enabled {
+        return Err(anyhow!("Auto-accept policy is not enabled"));
+    }
+
+    let happs = get_all_happs(&mut *wsm.lock().await).await?;
+    let log = load_log()?;
+    let undecided: Vec<&PresentedHappBundle> = happs
+        .iter()
+        .filter(|happ| is_undecided(happ, &policy, &log))
+        .collect();
+    if undecided.is_empty() {
+        return Ok(vec![]);
+    }
+    debug!("evaluating {} undecided happs", undecided.len());
+
+    let mut hha = CoreAppAgent::spawn(None).await?;
+    let host_preferences: HostPreferences = hha.get_host_preferences().await?;
+    let host_prices = PublisherPricingPref {
+        cpu: host_preferences.price_compute,
+        storage: host_preferences.price_storage,
+        bandwidth: host_preferences.price_bandwidth,
+    };
+    let host_jurisdiction = if policy.check_jurisdiction {
+        Some(hbs.download_holo_client().await?.jurisdiction)
+    } else {
+        None
+    };
+    let mut hosted = happs
+        .iter()
+        .filter(|happ| happ.host_settings.is_enabled)
+        .count();
+
+    let mut decisions = vec![];
+    for happ in undecided {
+        let reasons = evaluate(
+            happ,
+            &policy,
+            &host_prices,
+            host_jurisdiction.as_deref(),
+            hosted,
+        );
+
+        let decision = if reasons.is_empty() {
+            hosted += 1;
+            let (job, created) = jobs.lock().await.create(&happ.id.to_string());
+            if !created {
+                // happ stays undecided, it is not any more once the running job enables it
+                info!(
+                    "happ {} is already being installed by job {}",
+                    happ.id, job.id
+                );
+                continue;
+            }
+            info!("auto-accepting happ {} as install job {}", happ.id, job.id);
+            let progress = JobProgress::new(job.id, jobs.clone());
+            let payload = install::InstallHappBody {
+                happ_id: happ.id.to_string(),
+                membrane_proofs: HashMap::new(),
+            };
+            match install::handle_install_app(wsm, payload, &progress).await {
+                Ok(_) => AutoAcceptDecision::new(happ, Decision::Accepted, vec![], Some(job.id)),
+                Err(e) => {
+                    hosted -= 1;
+                    AutoAcceptDecision::new(
+                        happ,
+                        Decision::Failed,
+                        vec![e.to_string()],
+                        Some(job.id),
+                    )
+                }
+            }
+        } else {
+            AutoAcceptDecision::new(happ, Decision::Rejected, reasons, None)
+        };
+
+        if let Err(e) = append_log(decision.clone()) {
+            warn!("failed to record auto-accept decision: {}", e);
+        }
+        decisions.push(decision);
+    }
+
+    Ok(decisions)
+}
+
+/// Happ is undecided when it is published, host has neither enabled nor disabled it
+/// and there is no decision on it since the policy was last changed
+fn is_undecided(
+    happ: &PresentedHappBundle,
+    policy: &AutoAcceptPolicy,
+    log: &AutoAcceptLog,
+) -> bool {
+    if happ.is_draft
+        || happ.is_paused
+        || happ.special_installed_app_id.is_some()
+        || happ.host_settings.is_enabled
+        || happ.host_settings.is_host_disabled
+        || happ.host_settings.is_auto_disabled
+    {
+        return false;
+    }
+
+    let id = happ.id.to_string();
+    !log.decisions.iter().any(|decision| {
+        decision.happ_id == id
+            && policy
+                .updated_at
+                .map_or(true, |updated| decision.decided_at >= updated)
+    })
+}
+
+/// Returns reasons why happ does not match the policy, empty when it matches
+fn evaluate(
+    happ: &PresentedHappBundle,
+    policy: &AutoAcceptPolicy,
+    host_prices: &PublisherPricingPref,
+    host_jurisdiction: Option<&str>,
+    hosted: usize,
+) -> Vec<String> {
+    let mut reasons = vec![];
+
+    if let Some(max) = policy.max_hosted_happs {
+        if hosted >= max {
+            reasons.push(format!(
+                "{} happs are hosted already, maximum is {}",
+                hosted, max
+            ));
+        }
+    }
+
+    if let Some(percent) = policy.min_price_percent {
+        reasons.extend(check_prices(
+            &happ.publisher_pricing_pref,
+            host_prices,
+            percent,
+        ));
+    }
+
+    if !policy.categories.is_empty()
+        && !happ
+            .categories
+            .iter()
+            .any(|c| policy.categories.iter().any(|p| p.eq_ignore_ascii_case(c)))
+    {
+        reasons.push(format!(
+            "Happ's categories {} are not among accepted categories {}",
+            happ.categories.join(", "),
+            policy.categories.join(", ")
+        ));
+    }
+
+    if let Some(host_jurisdiction) = host_jurisdiction {
+        let check = check_jurisdiction(
+            &happ.jurisdictions,
+            happ.exclude_jurisdictions,
+            host_jurisdiction,
+        );
+        if check.status == CheckStatus::Fail {
+            reasons.push(check.reason);
+        }
+    }
+
+    reasons
+}
+
+/// Checks that each of publisher's prices is at least `percent` % of host's price
+fn check_prices(
+    publisher: &PublisherPricingPref,
+    host: &PublisherPricingPref,
+    percent: u32,
+) -> Vec<String> {
+    [
+        ("cpu", publisher.cpu, host.cpu),
+        ("storage", publisher.storage, host.storage),
+        ("bandwidth", publisher.bandwidth, host.bandwidth),
+    ]
+    .iter()
+    .filter_map(|(name, offered, asked)| {
+        match (
+            fuel::multiply_units(*offered, 100),
+            fuel::multiply_units(*asked, percent),
+        ) {
+            (Ok(offered_units), Ok(asked_units)) => {
+                if offered_units < asked_units {
+                    Some(format!(
+                        "{} price {} is below {}% of host's price {}",
+                        name, offered, percent, asked
+                    ))
+                } else {
+                    None
+                }
+            }
+            _ => Some(format!(
+                "{} price {} can not be compared to {}",
+                name, offered, asked
+            )),
+        }
+    })
+    .collect()
+}
+
+fn load_policy() -> Result<AutoAcceptPolicy> {
+    load_json(&get_data_dir().join("auto_accept.json"))
+}
+
+fn load_log() -> Result<AutoAcceptLog> {
+    load_json(&get_data_dir().join("auto_accept_log.json"))
+}
+
+fn append_log(decision: AutoAcceptDecision) -> Result<()> {
+    let mut log = load_log()?;
+    log.decisions.push(decision);
+    let excess = log.decisions.len().saturating_sub(MAX_LOG_ENTRIES);
+    log.decisions.drain(..excess);
+    save_json(&get_data_dir().join("auto_accept_log.json"), &log)
+}
+
+/// Criteria that are not set are not checked
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct AutoAcceptPolicy {
+    pub enabled: bool,
+    pub min_price_percent: Option<u32>, // of host's default prices, 100 means at least host's prices
+    #[serde(default)]
+    pub categories: Vec<String>, // empty accepts all categories
+    #[serde(default)]
+    pub check_jurisdiction: bool,
+    pub max_hosted_happs: Option<usize>,
+    #[serde(default)]
+    pub updated_at: Option<Timestamp>, // set when policy is saved
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(crate = "rocket::serde")]
+struct AutoAcceptLog {
+    decisions: Vec<AutoAcceptDecision>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub enum Decision {
+    Accepted,
+    Rejected,
+    Failed, // happ matched the policy, but its installation failed
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct AutoAcceptDecision {
+    pub happ_id: String,
+    pub happ_name: String,
+    pub decision: Decision,
+    pub reasons: Vec<String>, // why happ was rejected or its installation failed
+    pub install_job: Option<u64>,
+    pub decided_at: Timestamp,
+}
+
+impl AutoAcceptDecision {
+    fn new(
+        happ: &PresentedHappBundle,
+        decision: Decision,
+        reasons: Vec<String>,
+        install_job: Option<u64>,
+    ) -> Self {
+        AutoAcceptDecision {
+            happ_id: happ.id.to_string(),
+            happ_name: happ.name.clone(),
+            decision,
+            reasons,
+            install_job,
+            decided_at: Timestamp::now(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use holofuel_types::fuel::Fuel;
+
+    use super::check_prices;
+    use crate::common::types::PublisherPricingPref;
+
+    fn prices(cpu: &str, storage: &str, bandwidth: &str) -> PublisherPricingPref {
+        PublisherPricingPref {
+            cpu: Fuel::from_str(cpu).unwrap(),
+            storage: Fuel::from_str(storage).unwrap(),
+            bandwidth: Fuel::from_str(bandwidth).unwrap(),
+        }
+    }
+
+    #[test]
+    fn compares_prices_relative_to_host_prices() {
+        let host = prices("1", "2", "0.1");
+
+        assert!(check_prices(&prices("1", "2", "0.1"), &host, 100).is_empty());
+        assert!(check_prices(&prices("0.5", "1", "0.05"), &host, 50).is_empty());
+
+        let too_low = check_prices(&prices("0.5", "2", "0.01"), &host, 100);
+        assert_eq!(too_low.len(), 2);
+        assert!(too_low[0].starts_with("cpu price"));
+        assert!(too_low[1].starts_with("bandwidth price"));
+
+        // prices too large to compare are rejected rather than overflowing
+        let huge = check_prices(&prices("100000000000000", "2", "0.1"), &host, 100);
+        assert_eq!(huge.len(), 1);
+        assert!(huge[0].starts_with("cpu price"));
+        assert!(huge[0].contains("can not be compared"));
+    }
+}
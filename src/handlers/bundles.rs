@@ -0,0 +1,599 @@
+/*
+Bundle Cache Overview
+
+Bundles downloaded for installation are kept in `<HPOS_API_DATA_DIR>/bundles`, named by sha256 of their content.
+An index maps each bundle url to the content it served last, so that:
+- the base servicelogger bundle is downloaded once and reused for every hosted happ
+- the same bundle served from several urls is stored once
+- a happ can be reinstalled while its bundle url is unreachable
+
+Once the total size of cached bundles exceeds HPOS_API_BUNDLE_CACHE_MAX_SIZE bytes (1 GiB by default),
+least recently used bundles are evicted. Bundles in use by a running installation are pinned and never
+evicted or purged until the installation is done with them. Bundles from local files (`file://` urls) are never cached.
+
+The index also records the url and content each hosted happ instance was installed from, together with membrane
+proofs it was installed with, so that an upgrade can roll back to the running version even after the publisher
+has moved the happ to another url. Bundles recorded for an installed instance are never evicted or purged,
+they are removed once the instance is uninstalled.
+*/
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use holochain_types::prelude::{MembraneProof, Timestamp};
+use log::{debug, warn};
+use rocket::serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::common::store::{load_json, save_json};
+use crate::hpos::get_data_dir;
+
+const DEFAULT_MAX_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Guards the index of cache against concurrent installs, held only for synchronous file operations
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+/// Hashes of cached bundles in use, once for each `BundleFile` that is alive
+static PINNED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Local copy of a bundle. Cached bundle is pinned in cache until this is dropped.
+pub struct BundleFile {
+    pub path: PathBuf,
+    pinned: Option<String>, // hash of cached bundle, None for local files
+}
+
+impl Drop for BundleFile {
+    fn drop(&mut self) {
+        if let Some(hash) = self.pinned.take() {
+            if let Ok(mut pinned) = PINNED.lock() {
+                if let Some(i) = pinned.iter().position(|h| *h == hash) {
+                    pinned.remove(i);
+                }
+            }
+        }
+    }
+}
+
+/// Returns a local copy of bundle from `url`.
+/// Cached copy is used whenever there is one, unless `refresh` is set,
+/// in which case the bundle is downloaded again and cached copy is only a fallback for unreachable `url`.
+pub async fn get_bundle(url: &Url, refresh: bool) -> Result<BundleFile> {
+    // Local bundles are not ours to manage
+    if url.scheme() == "file" {
+        return Ok(BundleFile {
+            path: hpos_hc_connect::utils::download_file(url).await?,
+            pinned: None,
+        });
+    }
+
+    if !refresh {
+        if let Some(bundle) = lookup(url)? {
+            debug!("using cached bundle {:?} for {}", bundle.path, url);
+            return Ok(bundle);
+        }
+    }
+
+    match hpos_hc_connect::utils::download_file(url).await {
+        Ok(downloaded_path) => store(url, &downloaded_path),
+        Err(e) => match lookup(url)? {
+            Some(bundle) => {
+                warn!(
+                    "Failed to download {}, using cached bundle {:?} instead: {}",
+                    url, bundle.path, e
+                );
+                Ok(bundle)
+            }
+            None => Err(e),
+        },
+    }
+}
+
+/// Lists cached bundles, most recently used first
+pub fn handle_get_cache() -> Result<BundleCacheReport> {
+    let _lock = lock_index()?;
+    let mut index = load_index()?;
+    index.bundles.sort_by(|a, b| b.last_used.cmp(&a.last_used));
+
+    Ok(BundleCacheReport {
+        max_size: get_max_size(),
+        total_size: index.bundles.iter().map(|b| b.size).sum(),
+        bundles: index.bundles,
+    })
+}
+
+/// Removes bundle with content `hash` from cache, or all bundles when `hash` is not provided.
+/// Returns removed bundles.
+pub fn handle_purge_cache(hash: Option<&str>) -> Result<Vec<CachedBundle>> {
+    let _lock = lock_index()?;
+    let mut index = load_index()?;
+    let pinned = get_kept(&index)?;
+
+    if let Some(hash) = hash {
+        if !index.bundles.iter().any(|b| b.hash == hash) {
+            return Err(anyhow!("Bundle {} is not cached", hash));
+        }
+        if pinned.iter().any(|h| h == hash) {
+            return Err(anyhow!(
+                "Bundle {} is in use by a running installation or by an installed app",
+                hash
+            ));
+        }
+    }
+
+    // bundles in use are left in cache when purging all of them
+    let (removed, kept): (Vec<CachedBundle>, Vec<CachedBundle>) = index
+        .bundles
+        .into_iter()
+        .partition(|b| hash.map_or(true, |hash| b.hash == hash) && !pinned.contains(&b.hash));
+
+    for bundle in removed.iter() {
+        remove_file(bundle);
+    }
+    index.bundles = kept;
+    save_index(&index)?;
+
+    Ok(removed)
+}
+
+/// Forgets the bundle `installed_app_id` was installed from and removes bundles cached for it or for `urls`,
+/// unless they also serve one of `used_urls` or are in use by a running installation or another installed app.
+/// With `dry_run` nothing is removed. Returns bundles that are (or would be) removed.
+pub fn remove_unused(
+    installed_app_id: &str,
+    urls: &[String],
+    used_urls: &[String],
+    dry_run: bool,
+) -> Result<Vec<CachedBundle>> {
+    let _lock = lock_index()?;
+    let mut index = load_index()?;
+
+    let (installed, others): (Vec<InstalledBundle>, Vec<InstalledBundle>) = index
+        .installs
+        .into_iter()
+        .partition(|install| install.installed_app_id == installed_app_id);
+    index.installs = others;
+
+    let hashes: Vec<String> = installed
+        .iter()
+        .filter_map(|install| install.hash.clone())
+        .collect();
+    let unused = select_unused(&index.bundles, urls, &hashes, used_urls, &get_kept(&index)?);
+
+    let (removed, kept): (Vec<CachedBundle>, Vec<CachedBundle>) = index
+        .bundles
+        .into_iter()
+        .partition(|b| unused.contains(&b.hash));
+
+    if !dry_run {
+        for bundle in removed.iter() {
+            debug!("removing bundle {} from cache", bundle.hash);
+            remove_file(bundle);
+        }
+        index.bundles = kept;
+        save_index(&index)?;
+    }
+
+    Ok(removed)
+}
+
+/// Records that `installed_app_id` has been installed from `bundle` downloaded from `url` with `membrane_proofs`
+pub fn record_install(
+    installed_app_id: &str,
+    url: &str,
+    bundle: &BundleFile,
+    membrane_proofs: HashMap<String, MembraneProof>,
+) -> Result<()> {
+    let _lock = lock_index()?;
+    let mut index = load_index()?;
+
+    index
+        .installs
+        .retain(|install| install.installed_app_id != installed_app_id);
+    index.installs.push(InstalledBundle {
+        installed_app_id: installed_app_id.to_string(),
+        url: url.to_string(),
+        hash: bundle.pinned.clone(),
+        membrane_proofs,
+        installed_at: Timestamp::now(),
+    });
+    save_index(&index)
+}
+
+/// Forgets the bundle `installed_app_id` was installed from, it can be evicted from now on
+pub fn forget_install(installed_app_id: &str) -> Result<()> {
+    let _lock = lock_index()?;
+    let mut index = load_index()?;
+
+    index
+        .installs
+        .retain(|install| install.installed_app_id != installed_app_id);
+    save_index(&index)
+}
+
+/// Returns record of the bundle `installed_app_id` was installed from,
+/// None for apps installed before these records were kept
+pub fn get_install(installed_app_id: &str) -> Result<Option<InstalledBundle>> {
+    let _lock = lock_index()?;
+    Ok(load_index()?
+        .installs
+        .into_iter()
+        .find(|install| install.installed_app_id == installed_app_id))
+}
+
+/// Returns a local copy of the bundle `install` was installed from, None when it is gone
+pub async fn get_installed_bundle(install: &InstalledBundle) -> Result<Option<BundleFile>> {
+    match &install.hash {
+        Some(hash) => lookup_hash(hash),
+        // local bundles are not cached, the file is used again
+        None => {
+            let url = Url::parse(&install.url)?;
+            Ok(hpos_hc_connect::utils::download_file(&url)
+                .await
+                .ok()
+                .map(|path| BundleFile { path, pinned: None }))
+        }
+    }
+}
+
+fn lookup_hash(hash: &str) -> Result<Option<BundleFile>> {
+    let _lock = lock_index()?;
+    let mut index = load_index()?;
+
+    let bundle = match index.bundles.iter_mut().find(|b| b.hash == hash) {
+        Some(bundle) => bundle,
+        None => return Ok(None),
+    };
+
+    let path = get_cache_dir().join(&bundle.file_name);
+    if !path.exists() {
+        warn!("cached bundle {:?} is missing", path);
+        return Ok(None);
+    }
+    bundle.last_used = Timestamp::now();
+    let bundle = pin(path, hash)?;
+    save_index(&index)?;
+
+    Ok(Some(bundle))
+}
+
+fn lookup(url: &Url) -> Result<Option<BundleFile>> {
+    let _lock = lock_index()?;
+    let mut index = load_index()?;
+
+    let bundle = match index
+        .bundles
+        .iter_mut()
+        .find(|b| b.urls.iter().any(|u| u == url.as_str()))
+    {
+        Some(bundle) => bundle,
+        None => return Ok(None),
+    };
+
+    let path = get_cache_dir().join(&bundle.file_name);
+    if !path.exists() {
+        warn!(
+            "cached bundle {:?} is missing, it will be downloaded again",
+            path
+        );
+        return Ok(None);
+    }
+    bundle.last_used = Timestamp::now();
+    let bundle = pin(path, &bundle.hash)?;
+    save_index(&index)?;
+
+    Ok(Some(bundle))
+}
+
+/// Moves downloaded bundle into cache and evicts least recently used bundles if cache has grown too big
+fn store(url: &Url, downloaded_path: &Path) -> Result<BundleFile> {
+    let hash = sha256_file(downloaded_path)?;
+    let size = fs::metadata(downloaded_path)?.len();
+    let extension = downloaded_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("happ");
+    let file_name = format!("{}.{}", hash, extension);
+
+    let _lock = lock_index()?;
+    let mut index = load_index()?;
+
+    let cache_dir = get_cache_dir();
+    fs::create_dir_all(&cache_dir)?;
+    let path = cache_dir.join(&file_name);
+    if !path.exists() {
+        // download may live on another file system than cache, so rename can not be relied on
+        fs::copy(downloaded_path, &path)?;
+    }
+    if let Err(e) = fs::remove_file(downloaded_path) {
+        warn!(
+            "Failed to remove downloaded file {:?}: {}",
+            downloaded_path, e
+        );
+    }
+
+    // url now serves this content only
+    for bundle in index.bundles.iter_mut() {
+        bundle.urls.retain(|u| u != url.as_str());
+    }
+    let now = Timestamp::now();
+    match index.bundles.iter_mut().find(|b| b.hash == hash) {
+        Some(bundle) => {
+            bundle.urls.push(url.to_string());
+            bundle.last_used = now;
+        }
+        None => index.bundles.push(CachedBundle {
+            hash: hash.clone(),
+            file_name,
+            size,
+            urls: vec![url.to_string()],
+            cached_at: now,
+            last_used: now,
+        }),
+    }
+
+    let bundle = pin(path, &hash)?;
+    let evicted = select_evictions(&index.bundles, get_max_size(), &get_kept(&index)?);
+    index.bundles.retain(|b| {
+        if evicted.contains(&b.hash) {
+            debug!("evicting bundle {} from cache", b.hash);
+            remove_file(b);
+            false
+        } else {
+            true
+        }
+    });
+    save_index(&index)?;
+
+    Ok(bundle)
+}
+
+/// Returns hashes of least recently used bundles that have to go for cache to fit in `max_size`.
+/// Bundles in `pinned` are in use and never evicted.
+fn select_evictions(bundles: &[CachedBundle], max_size: u64, pinned: &[String]) -> Vec<String> {
+    let mut total: u64 = bundles.iter().map(|b| b.size).sum();
+    let mut candidates: Vec<&CachedBundle> = bundles
+        .iter()
+        .filter(|b| !pinned.contains(&b.hash))
+        .collect();
+    candidates.sort_by(|a, b| a.last_used.cmp(&b.last_used));
+
+    let mut evicted = vec![];
+    for bundle in candidates {
+        if total <= max_size {
+            break;
+        }
+        total -= bundle.size;
+        evicted.push(bundle.hash.clone());
+    }
+    evicted
+}
+
+/// Returns hashes of bundles that served one of `urls` or are one of `hashes`, and served none of `used_urls`,
+/// except those in `pinned`
+fn select_unused(
+    bundles: &[CachedBundle],
+    urls: &[String],
+    hashes: &[String],
+    used_urls: &[String],
+    pinned: &[String],
+) -> Vec<String> {
+    bundles
+        .iter()
+        .filter(|b| b.urls.iter().any(|u| urls.contains(u)) || hashes.contains(&b.hash))
+        .filter(|b| !b.urls.iter().any(|u| used_urls.contains(u)))
+        .filter(|b| !pinned.contains(&b.hash))
+        .map(|b| b.hash.clone())
+        .collect()
+}
+
+fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn remove_file(bundle: &CachedBundle) {
+    let path = get_cache_dir().join(&bundle.file_name);
+    if let Err(e) = fs::remove_file(&path) {
+        warn!("Failed to remove cached bundle {:?}: {}", path, e);
+    }
+}
+
+/// Pins cached bundle `hash` at `path`, has to be called with index locked so that it is not evicted meanwhile
+fn pin(path: PathBuf, hash: &str) -> Result<BundleFile> {
+    lock_pinned()?.push(hash.to_string());
+    Ok(BundleFile {
+        path,
+        pinned: Some(hash.to_string()),
+    })
+}
+
+/// Hashes of bundles that can not be removed: those in use by a running installation and those of installed apps
+fn get_kept(index: &BundleIndex) -> Result<Vec<String>> {
+    let mut kept = lock_pinned()?.clone();
+    kept.extend(
+        index
+            .installs
+            .iter()
+            .filter_map(|install| install.hash.clone()),
+    );
+    Ok(kept)
+}
+
+fn lock_index() -> Result<std::sync::MutexGuard<'static, ()>> {
+    INDEX_LOCK
+        .lock()
+        .map_err(|_| anyhow!("Bundle cache index lock is poisoned"))
+}
+
+fn lock_pinned() -> Result<std::sync::MutexGuard<'static, Vec<String>>> {
+    PINNED
+        .lock()
+        .map_err(|_| anyhow!("Lock of pinned bundles is poisoned"))
+}
+
+fn get_cache_dir() -> PathBuf {
+    get_data_dir().join("bundles")
+}
+
+fn get_max_size() -> u64 {
+    std::env::var("HPOS_API_BUNDLE_CACHE_MAX_SIZE")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_MAX_SIZE)
+}
+
+fn load_index() -> Result<BundleIndex> {
+    load_json(&get_cache_dir().join("index.json"))
+}
+
+fn save_index(index: &BundleIndex) -> Result<()> {
+    save_json(&get_cache_dir().join("index.json"), index)
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(crate = "rocket::serde")]
+struct BundleIndex {
+    bundles: Vec<CachedBundle>,
+    #[serde(default)]
+    installs: Vec<InstalledBundle>,
+}
+
+/// Bundle an installed app has been installed from
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct InstalledBundle {
+    pub installed_app_id: String,
+    pub url: String,
+    pub hash: Option<String>, // None for local files, which are not cached
+    pub membrane_proofs: HashMap<String, MembraneProof>,
+    pub installed_at: Timestamp,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct CachedBundle {
+    pub hash: String, // sha256 of content
+    pub file_name: String,
+    pub size: u64,
+    pub urls: Vec<String>, // urls that served this content last time they were downloaded
+    pub cached_at: Timestamp,
+    pub last_used: Timestamp,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct BundleCacheReport {
+    pub max_size: u64,
+    pub total_size: u64,
+    pub bundles: Vec<CachedBundle>,
+}
+
+#[cfg(test)]
+mod test {
+    use holochain_types::prelude::{MembraneProof, Timestamp};
+
+    use super::{select_evictions, select_unused, CachedBundle};
+
+    fn bundle(hash: &str, size: u64, last_used: i64) -> CachedBundle {
+        with_urls(hash, size, last_used, &[])
+    }
+
+    fn with_urls(hash: &str, size: u64, last_used: i64, urls: &[&str]) -> CachedBundle {
+        CachedBundle {
+            hash: hash.to_string(),
+            file_name: format!("{}.happ", hash),
+            size,
+            urls: urls.iter().map(|u| u.to_string()).collect(),
+            cached_at: Timestamp::from_micros(0),
+            last_used: Timestamp::from_micros(last_used),
+        }
+    }
+
+    #[test]
+    fn evicts_least_recently_used_bundles() {
+        let bundles = vec![
+            bundle("a", 40, 3),
+            bundle("b", 40, 1),
+            bundle("c", 40, 2),
+            bundle("d", 40, 0),
+        ];
+
+        let pinned =
+            |hashes: &[&str]| -> Vec<String> { hashes.iter().map(|h| h.to_string()).collect() };
+
+        // fits
+        assert!(select_evictions(&bundles, 160, &pinned(&["a"])).is_empty());
+        // "d" is the least recently used one, but it is in use
+        assert_eq!(
+            select_evictions(&bundles, 100, &pinned(&["d"])),
+            vec!["b", "c"]
+        );
+        // nothing else to evict
+        assert_eq!(
+            select_evictions(&bundles, 10, &pinned(&["a"])),
+            vec!["d", "b", "c"]
+        );
+        // bundles used by concurrent installs are kept even over max size
+        assert_eq!(
+            select_evictions(&bundles, 10, &pinned(&["a", "b", "c"])),
+            vec!["d"]
+        );
+    }
+
+    #[test]
+    fn selects_bundles_used_only_by_given_urls() {
+        let bundles = vec![
+            with_urls("a", 40, 0, &["https://a.com/a.happ"]),
+            // same content served for another happ
+            with_urls(
+                "b",
+                40,
+                0,
+                &["https://a.com/b.happ", "https://b.com/b.happ"],
+            ),
+            with_urls("c", 40, 0, &["https://c.com/c.happ"]),
+            with_urls("d", 40, 0, &["https://a.com/d.happ"]),
+        ];
+        let urls: Vec<String> = [
+            "https://a.com/a.happ",
+            "https://a.com/b.happ",
+            "https://a.com/d.happ",
+        ]
+        .iter()
+        .map(|u| u.to_string())
+        .collect();
+        let used_urls = vec!["https://b.com/b.happ".to_string()];
+
+        assert_eq!(
+            select_unused(&bundles, &urls, &[], &used_urls, &[]),
+            vec!["a", "d"]
+        );
+        // "d" is in use by a running installation
+        assert_eq!(
+            select_unused(&bundles, &urls, &[], &used_urls, &["d".to_string()]),
+            vec!["a"]
+        );
+        // happ was installed from "c" before it moved to another url
+        assert_eq!(
+            select_unused(&bundles, &urls, &["c".to_string()], &used_urls, &[]),
+            vec!["a", "c", "d"]
+        );
+    }
+}
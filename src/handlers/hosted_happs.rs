@@ -1,18 +1,32 @@
 use holochain_client::AgentPubKey;
 use holochain_types::prelude::{
-    holochain_serial, Entry, Record, RecordEntry, SerializedBytes, Signature, Timestamp,
+    holochain_serial, ActionType, ChainQueryFilter, ChainQueryFilterRange, Entry, Record,
+    RecordEntry, SerializedBytes, Signature, Timestamp,
 };
 use hpos_hc_connect::app_connection::CoreAppRoleName;
-use rocket::serde::{Deserialize, Serialize};
+use rocket::{
+    serde::{Deserialize, Serialize},
+    FromForm, FromFormField,
+};
 
+use super::hosting_history::{self, HostingEventKind};
+use super::install::handle_holochain_enable;
+use super::quota;
+use super::visibility;
+use crate::common::keypair::Keys;
+use crate::common::series::{timestamp_to_date_time, Buckets};
 use crate::common::types::{HappAndHost, PresentedHappBundle, Transaction, POS};
 use crate::hpos::Ws;
 use crate::HappDetails;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
 use holochain_types::dna::{ActionHash, ActionHashB64, DnaHashB64};
-use log::debug;
+use holofuel_types::fuel::Fuel;
+use log::{debug, warn};
 use std::collections::HashMap;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
 
 type AllTransactions = HashMap<ActionHashB64, Vec<Transaction>>;
 
@@ -22,7 +36,7 @@ pub struct InvoiceNote {
     pub hha_id: ActionHashB64,
 }
 
-// fetch all transactions for every hApp
+// fetch all transactions for every hApp that is not hidden by host's visibility rules
 pub async fn handle_get_all(
     usage_interval: i64,
     quantity: Option<usize>,
@@ -43,17 +57,22 @@ pub async fn handle_get_all(
     // Ask holofuel for all transactions so that I can calculate earings - isn't it ridiculous?
     let mut all_transactions = get_all_transactions(ws).await?;
 
+    let rules = visibility::handle_get_rules()?;
+
     let mut result: Vec<HappDetails> = vec![];
-    for happ in all_hosted_happs.iter() {
-        result.push(
-            HappDetails::init(
-                happ,
-                all_transactions.remove(&happ.id).unwrap_or(vec![]),
-                usage_interval,
-                ws,
-            )
-            .await,
-        );
+    for happ in all_hosted_happs
+        .iter()
+        .filter(|happ| !rules.is_hidden(happ))
+    {
+        let mut details = HappDetails::init(
+            happ,
+            all_transactions.remove(&happ.id).unwrap_or(vec![]),
+            usage_interval,
+            ws,
+        )
+        .await;
+        details.is_pinned = rules.is_pinned(happ);
+        result.push(details);
     }
 
     // sort vec by earnings.last_7_days in decreasing order
@@ -63,6 +82,9 @@ pub async fn handle_get_all(
         a.last_7_days.cmp(&b.last_7_days)
     });
 
+    // pinned happs go first, keeping the order above within pinned and not pinned ones
+    result.sort_by_key(|happ| !happ.is_pinned);
+
     // take first `quantity` only
     if let Some(q) = quantity {
         result.truncate(q);
@@ -92,13 +114,53 @@ pub async fn handle_get_one(
     // Ask holofuel for all transactions so that I can calculate earings - isn't it ridiculous?
     let mut all_transactions = get_all_transactions(ws).await?;
 
-    Ok(HappDetails::init(
+    let mut details = HappDetails::init(
         &happ,
         all_transactions.remove(&happ.id).unwrap_or(vec![]),
         usage_interval,
         ws,
     )
-    .await)
+    .await;
+    details.is_pinned = visibility::handle_get_rules()?.is_pinned(&happ);
+
+    Ok(details)
+}
+
+/// Returns earnings from invoices for happ `id` completed within range of `buckets`, summed up per bucket
+pub async fn handle_get_earnings_series(
+    ws: &mut Ws,
+    id: ActionHashB64,
+    buckets: &Buckets,
+) -> Result<Vec<EarningsPoint>> {
+    let transactions = get_all_transactions(ws)
+        .await?
+        .remove(&id)
+        .unwrap_or_default();
+
+    let mut series: Vec<EarningsPoint> = buckets
+        .starts
+        .iter()
+        .map(|start| EarningsPoint {
+            start: start.to_rfc3339(),
+            earnings: Fuel::new(0),
+            invoices: 0,
+        })
+        .collect();
+
+    for tx in transactions {
+        if let Some(completed_date) = tx.completed_date {
+            let completed_date = timestamp_to_date_time(completed_date);
+            if let Some(point) = buckets
+                .index_of(completed_date)
+                .and_then(|i| series.get_mut(i))
+            {
+                point.earnings = (point.earnings + Fuel::from_str(&tx.amount)?)?;
+                point.invoices += 1;
+            }
+        }
+    }
+
+    Ok(series)
 }
 
 /// get all holofuel transactions and organize in HashMap by happ_id extracted from invoice's note
@@ -137,10 +199,9 @@ async fn get_all_transactions(ws: &mut Ws) -> Result<AllTransactions> {
 }
 
 /// Enable happ for hosting in core happ
-pub async fn handle_enable(ws: &mut Ws, id: &str) -> Result<()> {
-    let payload = HappAndHost::init(id).await?;
-
+pub async fn handle_enable(ws: &mut Ws, payload: HappAndHost) -> Result<()> {
     debug!("calling zome hha/enable_happ with payload: {:?}", &payload);
+    let happ_id = payload.happ_id.clone();
     let app_connection = ws.get_connection(ws.core_app_id.clone()).await?;
 
     app_connection
@@ -152,12 +213,20 @@ pub async fn handle_enable(ws: &mut Ws, id: &str) -> Result<()> {
         )
         .await?;
 
+    hosting_history::record_event(&happ_id, HostingEventKind::Enabled);
+
+    // Happ auto-disabled over its disk quota has its instance stopped in holochain too
+    if quota::clear_auto_disabled(&happ_id)? {
+        handle_holochain_enable(&mut ws.admin, &happ_id.to_string()).await?;
+    }
+
     Ok(())
 }
 
 /// Disable happ for hosting in core happ
 pub async fn handle_disable(ws: &mut Ws, payload: HappAndHost) -> Result<()> {
     debug!("calling zome hha/disable_happ with payload: {:?}", &payload);
+    let happ_id = payload.happ_id.clone();
     let app_connection = ws.get_connection(ws.core_app_id.clone()).await?;
 
     app_connection
@@ -169,17 +238,249 @@ pub async fn handle_disable(ws: &mut Ws, payload: HappAndHost) -> Result<()> {
         )
         .await?;
 
+    hosting_history::record_event(&happ_id, HostingEventKind::Disabled);
+
     Ok(())
 }
 
-/// Get service logs for last `days` days for happ with id `id`
+/// Enable or disable many happs at once. Happs are selected by id and/or by filter.
+/// Holoport id is read from hpos config only once for the whole batch.
+/// Happs that could not be matched against filter are reported as failed.
+pub async fn handle_bulk_hosting(
+    ws: &mut Ws,
+    body: BulkHostingBody,
+) -> Result<Vec<BulkHostingResult>> {
+    let mut happ_ids = body.happ_ids.clone();
+    let mut results = vec![];
+    if let Some(filter) = body.filter {
+        for (id, matched) in get_filtered_happ_ids(ws, filter).await? {
+            match matched {
+                // explicitly listed happs are acted on regardless of filter
+                Ok(()) | Err(_) if happ_ids.contains(&id) => {}
+                Ok(()) => happ_ids.push(id),
+                Err(e) => {
+                    warn!("failed to match happ {} against filter: {}", &id, e);
+                    results.push(BulkHostingResult {
+                        id,
+                        success: false,
+                        error: Some(e.to_string()),
+                    });
+                }
+            }
+        }
+    }
+
+    let holoport_id = Keys::new().await?.holoport_id;
+
+    for id in happ_ids {
+        let result = match ActionHashB64::from_b64_str(&id) {
+            Ok(happ_id) => {
+                let payload = HappAndHost {
+                    happ_id,
+                    holoport_id: holoport_id.clone(),
+                };
+                match body.action {
+                    BulkAction::Enable => handle_enable(ws, payload).await,
+                    BulkAction::Disable => handle_disable(ws, payload).await,
+                }
+            }
+            Err(e) => Err(anyhow!("Invalid happ id: {}", e)),
+        };
+
+        if let Err(e) = &result {
+            warn!("failed to {:?} happ {}: {}", body.action, &id, e);
+        }
+
+        results.push(BulkHostingResult {
+            id,
+            success: result.is_ok(),
+            error: result.err().map(|e| e.to_string()),
+        });
+    }
+
+    Ok(results)
+}
+
+/// Returns hosting plan of happ `happ_id`, which is free when all its prices in hha are 0
+pub async fn get_plan(happ_id: ActionHashB64, ws: &mut Ws) -> Result<Option<HostingPlan>> {
+    let app_connection = ws.get_connection(ws.core_app_id.clone()).await?;
+
+    let s: ServiceloggerHappPreferences = app_connection
+        .zome_call_typed(
+            CoreAppRoleName::HHA.into(),
+            "hha".into(),
+            "get_happ_preferences".into(),
+            happ_id,
+        )
+        .await?;
+
+    if s.price_compute == Fuel::new(0)
+        && s.price_storage == Fuel::new(0)
+        && s.price_bandwidth == Fuel::new(0)
+    {
+        Ok(Some(HostingPlan::Free))
+    } else {
+        Ok(Some(HostingPlan::Paid))
+    }
+}
+
+/// Returns ids of all happs from hha that match every criteria of `filter`,
+/// together with happs that could not be checked against it and the reason why
+async fn get_filtered_happ_ids(
+    ws: &mut Ws,
+    filter: HappFilter,
+) -> Result<Vec<(String, Result<()>)>> {
+    let all_hosted_happs = get_all_happs(ws).await?;
+
+    let mut result = vec![];
+    for happ in all_hosted_happs {
+        if let Some(category) = &filter.category {
+            if !happ.categories.contains(category) {
+                continue;
+            }
+        }
+        if let Some(hosting_plan) = &filter.hosting_plan {
+            match get_plan(happ.id.clone(), ws).await {
+                Ok(plan) if plan.as_ref() == Some(hosting_plan) => {}
+                Ok(_) => continue,
+                Err(e) => {
+                    result.push((
+                        happ.id.to_string(),
+                        Err(anyhow!("Failed to read hosting plan: {}", e)),
+                    ));
+                    continue;
+                }
+            }
+        }
+        result.push((happ.id.to_string(), Ok(())));
+    }
+
+    Ok(result)
+}
+
+/// Get a page of service logs of happ with id `id` that match `filter`, newest first.
+/// Page starts below `cursor` (action_seq of the last entry of previous page) if it is provided.
+/// Servicelogger can only narrow the query by action type and action sequence,
+/// the rest of `filter` is applied to deserialized entries.
 pub async fn handle_get_service_logs(
     ws: &mut Ws,
     id: ActionHashB64,
-    days: i32,
-) -> Result<Vec<LogEntry>> {
-    let filter = holochain_types::prelude::ChainQueryFilter::new().include_entries(true);
+    filter: LogFilter,
+    cursor: Option<u32>,
+    limit: usize,
+) -> Result<LogsPage> {
+    let mut query = ChainQueryFilter::new()
+        .include_entries(true)
+        .action_type(ActionType::Create);
+    if let Some(cursor) = cursor {
+        if cursor == 0 {
+            return Ok(LogsPage::default());
+        }
+        query = query.sequence_range(ChainQueryFilterRange::ActionSeqRange(0, cursor - 1));
+    }
+
+    let records = query_service_logs(ws, &id, query).await?;
+
+    log::debug!("filtering logs from {}", id);
+
+    let mut page = LogsPage::default();
+    // chain is returned in ascending order
+    for record in records.iter().rev() {
+        let time = timestamp_to_date_time(record.action().timestamp());
+        if time < filter.from {
+            // everything that follows is even older
+            break;
+        }
+        if time >= filter.to {
+            continue;
+        }
+
+        if let Some(entry) = to_log_entry(record) {
+            if !filter.matches(&entry) {
+                continue;
+            }
+            if page.entries.len() == limit {
+                // there is at least one more matching entry, so there is a next page
+                page.next_cursor = page.entries.last().map(|e| e.action_seq);
+                break;
+            }
+            page.entries.push(LogRecord {
+                action_seq: record.action().action_seq(),
+                timestamp: record.action().timestamp(),
+                entry,
+            });
+        }
+    }
+
+    Ok(page)
+}
+
+/// Returns usage of happ `id` within range of `buckets` summed up per bucket.
+/// Cpu and bandwidth come from activity logs, disk usage is the last disk usage snapshot in a bucket.
+pub async fn handle_get_usage_series(
+    ws: &mut Ws,
+    id: ActionHashB64,
+    buckets: &Buckets,
+) -> Result<Vec<UsagePoint>> {
+    let filter = ChainQueryFilter::new().include_entries(true);
+    let records = query_service_logs(ws, &id, filter).await?;
+
+    let mut series: Vec<UsagePoint> = buckets
+        .starts
+        .iter()
+        .map(|start| UsagePoint {
+            start: start.to_rfc3339(),
+            ..Default::default()
+        })
+        .collect();
 
+    for record in records.iter() {
+        let time = timestamp_to_date_time(record.action().timestamp());
+        let point = match buckets.index_of(time).and_then(|i| series.get_mut(i)) {
+            Some(point) => point,
+            None => continue,
+        };
+
+        // records come in the order of source chain, so later snapshots overwrite earlier ones
+        match to_log_entry(record) {
+            Some(LogEntry::ActivityLog(log)) => {
+                point.requests += 1;
+                point.cpu = point.cpu.saturating_add(log.response.host_metrics.cpu);
+                point.bandwidth = point
+                    .bandwidth
+                    .saturating_add(log.response.host_metrics.bandwidth);
+            }
+            Some(LogEntry::DiskUsageLog(log)) => {
+                point.disk_usage = Some(log.files.iter().map(|file| file.size).sum());
+            }
+            None => {}
+        }
+    }
+
+    Ok(series)
+}
+
+/// Returns all happs registered in hha
+pub async fn get_all_happs(ws: &mut Ws) -> Result<Vec<PresentedHappBundle>> {
+    let app_connection = ws.get_connection(ws.core_app_id.clone()).await?;
+
+    debug!("calling zome hha/get_happs");
+    app_connection
+        .zome_call_typed(
+            CoreAppRoleName::HHA.into(),
+            "hha".into(),
+            "get_happs".into(),
+            (),
+        )
+        .await
+}
+
+/// Queries source chain of servicelogger instance assigned to happ `id`
+pub async fn query_service_logs(
+    ws: &mut Ws,
+    id: &ActionHashB64,
+    filter: ChainQueryFilter,
+) -> Result<Vec<Record>> {
     let app_connection = ws.get_connection(format!("{}::servicelogger", id)).await?;
 
     log::debug!("getting logs for happ: {:?}::servicelogger", id);
@@ -192,33 +493,202 @@ pub async fn handle_get_service_logs(
         )
         .await?;
 
-    let four_weeks_ago = (SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards")
-        .as_secs()
-        - (days as u64 * 24 * 60 * 60)) as i64;
+    Ok(result)
+}
+
+/// Deserializes servicelogger's entry from `record`.
+/// Includes only App Entries (those listed in #[hdk_entry_defs] in DNA code), not holochain system entries
+pub fn to_log_entry(record: &Record) -> Option<LogEntry> {
+    if let RecordEntry::Present(Entry::App(bytes)) = record.entry() {
+        if let Ok(log_entry) = ActivityLog::try_from(bytes.clone().into_sb()) {
+            return Some(LogEntry::ActivityLog(Box::new(log_entry)));
+        } else if let Ok(log_entry) = DiskUsageLog::try_from(bytes.clone().into_sb()) {
+            return Some(LogEntry::DiskUsageLog(log_entry));
+        }
+    }
+    None
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct BulkHostingBody {
+    pub action: BulkAction,
+    #[serde(default)]
+    pub happ_ids: Vec<String>,
+    #[serde(default)]
+    pub filter: Option<HappFilter>,
+}
 
-    log::debug!("filtering logs from {}", id);
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub enum BulkAction {
+    Enable,
+    Disable,
+}
 
-    let filtered_result: Vec<LogEntry> = result
-        .into_iter()
-        .filter(|record| record.action().timestamp().as_seconds_and_nanos().0 > four_weeks_ago)
-        // include only App Entries (those listed in #[hdk_entry_defs] in DNA code),
-        // not holochain system entries
-        // and deserialize them into service logger's entries
-        .filter_map(|record| {
-            if let RecordEntry::Present(Entry::App(bytes)) = record.entry() {
-                if let Ok(log_entry) = ActivityLog::try_from(bytes.clone().into_sb()) {
-                    return Some(LogEntry::ActivityLog(Box::new(log_entry)));
-                } else if let Ok(log_entry) = DiskUsageLog::try_from(bytes.clone().into_sb()) {
-                    return Some(LogEntry::DiskUsageLog(log_entry));
-                }
+// All criteria that are set have to match for a happ to be selected
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct HappFilter {
+    pub hosting_plan: Option<HostingPlan>,
+    pub category: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub enum HostingPlan {
+    Free,
+    Paid,
+}
+
+impl fmt::Display for HostingPlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HostingPlan::Free => write!(f, "free"),
+            HostingPlan::Paid => write!(f, "paid"),
+        }
+    }
+}
+
+// return type of a zome call to hha/get_happ_preferences
+#[derive(Debug, Serialize, Deserialize, SerializedBytes, Clone)]
+pub struct ServiceloggerHappPreferences {
+    pub provider_pubkey: AgentPubKey,
+    pub max_fuel_before_invoice: Fuel,
+    pub price_compute: Fuel,
+    pub price_storage: Fuel,
+    pub price_bandwidth: Fuel,
+    pub max_time_before_invoice: Duration,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct EarningsPoint {
+    pub start: String, // RFC 3339 start of the bucket
+    pub earnings: Fuel,
+    pub invoices: u32,
+}
+
+/// Query params of `/hosted/<id>/logs`, all of them are optional
+#[derive(FromForm, Debug, Default)]
+pub struct LogsQuery {
+    pub days: Option<i32>,    // ignored when `from` is provided
+    pub from: Option<String>, // date or RFC 3339 date-time
+    pub to: Option<String>,   // date or RFC 3339 date-time
+    pub entry_type: Option<LogEntryType>,
+    pub agent: Option<String>, // pubkey of the web user
+    pub status_code: Option<i16>,
+    pub zome: Option<String>,
+    pub function: Option<String>,
+    pub source_ip: Option<String>,
+    pub cursor: Option<u32>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, FromFormField, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub enum LogEntryType {
+    ActivityLog,
+    DiskUsageLog,
+}
+
+/// Criteria a log entry has to meet. Criteria that are specific to activity logs exclude disk usage logs.
+#[derive(Debug, Clone)]
+pub struct LogFilter {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub entry_type: Option<LogEntryType>,
+    pub agent: Option<String>,
+    pub status_code: Option<i16>,
+    pub zome: Option<String>,
+    pub function: Option<String>,
+    pub source_ip: Option<String>,
+}
+
+impl LogFilter {
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        match entry {
+            LogEntry::DiskUsageLog(_) => {
+                self.entry_type.unwrap_or(LogEntryType::DiskUsageLog) == LogEntryType::DiskUsageLog
+                    && self.agent.is_none()
+                    && self.status_code.is_none()
+                    && self.zome.is_none()
+                    && self.function.is_none()
+                    && self.source_ip.is_none()
             }
-            None
-        })
-        .collect();
+            LogEntry::ActivityLog(log) => {
+                let call_spec = &log.request.request.call_spec;
+                let weblog = &log.response.weblog_compat;
+
+                self.entry_type.unwrap_or(LogEntryType::ActivityLog) == LogEntryType::ActivityLog
+                    && self
+                        .agent
+                        .as_ref()
+                        .map_or(true, |a| *a == log.request.agent_id.to_string())
+                    && self.status_code.map_or(true, |c| c == weblog.status_code)
+                    && self.zome.as_ref().map_or(true, |z| *z == call_spec.zome)
+                    && self
+                        .function
+                        .as_ref()
+                        .map_or(true, |f| *f == call_spec.function)
+                    && self
+                        .source_ip
+                        .as_ref()
+                        .map_or(true, |ip| *ip == weblog.source_ip)
+            }
+        }
+    }
+}
 
-    Ok(filtered_result)
+/// All log entries, or a page of them when paging params are given
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+#[serde(untagged)]
+pub enum LogsResponse {
+    All(Vec<LogEntry>),
+    Page(LogsPage),
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct LogsPage {
+    pub entries: Vec<LogRecord>,
+    pub next_cursor: Option<u32>, // None on the last page
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct LogRecord {
+    pub action_seq: u32,
+    pub timestamp: Timestamp,
+    pub entry: LogEntry,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct UsagePoint {
+    pub start: String, // RFC 3339 start of the bucket
+    pub requests: u64,
+    pub cpu: u64,
+    pub bandwidth: u64,
+    pub disk_usage: Option<u64>, // None when there was no disk usage snapshot in the bucket
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct BulkHostingResult {
+    pub id: String,
+    pub success: bool,
+    pub error: Option<String>,
 }
 
 // --------servicelogger data types---------
@@ -298,3 +768,100 @@ pub struct File {
     /// File size in bytes
     pub size: u64,
 }
+
+#[cfg(test)]
+mod test {
+    use chrono::{Days, Utc};
+    use holochain_client::AgentPubKey;
+    use holochain_types::{
+        dna::{ActionHash, DnaHash},
+        prelude::{Signature, Timestamp},
+    };
+
+    use super::*;
+
+    fn activity_log(status_code: i16, zome: &str) -> LogEntry {
+        let hash = ActionHash::from_raw_36(vec![0; 36]);
+        LogEntry::ActivityLog(Box::new(ActivityLog {
+            request: ClientRequest {
+                agent_id: AgentPubKey::from_raw_36(vec![1; 36]),
+                request: RequestPayload {
+                    host_id: "host".to_string(),
+                    timestamp: Timestamp::now(),
+                    hha_pricing_pref: hash.clone(),
+                    call_spec: CallSpec {
+                        args_hash: vec![],
+                        function: "function".to_string(),
+                        zome: zome.to_string(),
+                        role_name: "role_name".to_string(),
+                        hha_hash: hash,
+                    },
+                },
+                request_signature: Signature([0; 64]),
+            },
+            response: HostResponse {
+                host_metrics: HostMetrics {
+                    cpu: 1,
+                    bandwidth: 1,
+                },
+                weblog_compat: ExtraWebLogData {
+                    source_ip: "127.0.0.1".to_string(),
+                    status_code,
+                },
+            },
+        }))
+    }
+
+    fn disk_usage_log() -> LogEntry {
+        LogEntry::DiskUsageLog(DiskUsageLog {
+            files: vec![File {
+                associated_dna: DnaHash::from_raw_36(vec![2; 36]).into(),
+                extension: "sqlite3".to_string(),
+                size: 1024,
+            }],
+            source_chain_count: 1,
+        })
+    }
+
+    fn filter() -> LogFilter {
+        LogFilter {
+            from: Utc::now() - Days::new(1),
+            to: Utc::now(),
+            entry_type: None,
+            agent: None,
+            status_code: None,
+            zome: None,
+            function: None,
+            source_ip: None,
+        }
+    }
+
+    #[test]
+    fn filters_log_entries() {
+        assert!(filter().matches(&activity_log(200, "zome")));
+        assert!(filter().matches(&disk_usage_log()));
+
+        let only_activity = LogFilter {
+            entry_type: Some(LogEntryType::ActivityLog),
+            ..filter()
+        };
+        assert!(only_activity.matches(&activity_log(200, "zome")));
+        assert!(!only_activity.matches(&disk_usage_log()));
+
+        let errors = LogFilter {
+            status_code: Some(500),
+            ..filter()
+        };
+        assert!(errors.matches(&activity_log(500, "zome")));
+        assert!(!errors.matches(&activity_log(200, "zome")));
+        assert!(!errors.matches(&disk_usage_log()));
+
+        let by_zome_and_ip = LogFilter {
+            zome: Some("zome".to_string()),
+            source_ip: Some("127.0.0.1".to_string()),
+            ..filter()
+        };
+        assert!(by_zome_and_ip.matches(&activity_log(200, "zome")));
+        assert!(!by_zome_and_ip.matches(&activity_log(200, "other_zome")));
+    }
+}
@@ -0,0 +1,232 @@
+// History of hosting of each happ on this holoport. hha keeps only whether a happ is enabled now,
+// so every enable and disable done through this API is recorded in `<HPOS_API_DATA_DIR>/hosting_history.json`.
+// Installation enables happ through `handle_enable`, so it is recorded too.
+// Happs enabled before the history was recorded, or enabled and disabled in hha by other means,
+// are caught up with hha's current state by `sync_with_hha`. Such events are marked as estimated.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use holochain_types::{dna::ActionHashB64, prelude::Timestamp};
+use log::warn;
+use rocket::serde::{Deserialize, Serialize};
+
+use crate::common::store::{load_json, save_json};
+use crate::common::types::PresentedHappBundle;
+use crate::hpos::get_data_dir;
+
+const DAY_MICROS: i64 = 24 * 60 * 60 * 1_000_000;
+
+/// Records that happ `id` has been enabled or disabled. Repeated events of the same kind are recorded once,
+/// failure to record is only logged so that it does not fail the action itself.
+pub fn record_event(id: &ActionHashB64, kind: HostingEventKind) {
+    if let Err(e) = append_event(id, kind) {
+        warn!("failed to record {:?} event of happ {}: {}", kind, id, e);
+    }
+}
+
+/// Records an estimated event when `happ`'s state in hha differs from its recorded history.
+/// A happ enabled before any history was recorded is seeded as enabled at `hosted_since`,
+/// the earliest known evidence of hosting, e.g. its first invoice.
+pub fn sync_with_hha(happ: &PresentedHappBundle, hosted_since: Option<Timestamp>) {
+    let result = load_history().and_then(|mut history| {
+        let events = history.entry(happ.id.to_string()).or_default();
+        if reconcile(
+            events,
+            happ.host_settings.is_enabled,
+            hosted_since,
+            Timestamp::now(),
+        ) {
+            save_json(&get_data_dir().join("hosting_history.json"), &history)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        warn!("failed to sync hosting history of happ {}: {}", happ.id, e);
+    }
+}
+
+/// Returns hosting history of happ `id` summed up as of now
+pub fn handle_get_history(id: &ActionHashB64) -> Result<HostingHistory> {
+    let events = load_history()?.remove(&id.to_string()).unwrap_or_default();
+    Ok(HostingHistory::new(id.clone(), events, Timestamp::now()))
+}
+
+fn append_event(id: &ActionHashB64, kind: HostingEventKind) -> Result<()> {
+    let mut history = load_history()?;
+    let events = history.entry(id.to_string()).or_default();
+    if events.last().map(|event| event.kind) == Some(kind) {
+        return Ok(());
+    }
+    events.push(HostingEvent {
+        kind,
+        at: Timestamp::now(),
+        estimated: false,
+    });
+    save_json(&get_data_dir().join("hosting_history.json"), &history)
+}
+
+/// Appends an estimated event to `events` if their last state is not `is_enabled`, returns whether it did
+fn reconcile(
+    events: &mut Vec<HostingEvent>,
+    is_enabled: bool,
+    hosted_since: Option<Timestamp>,
+    now: Timestamp,
+) -> bool {
+    let kind = if is_enabled {
+        HostingEventKind::Enabled
+    } else {
+        HostingEventKind::Disabled
+    };
+    let last = events.last().map(|event| event.kind);
+    if last.unwrap_or(HostingEventKind::Disabled) == kind {
+        return false;
+    }
+
+    let at = match (last, hosted_since) {
+        // first event ever, hosting has started before history was recorded
+        (None, Some(since)) if since < now => since,
+        _ => now,
+    };
+    events.push(HostingEvent {
+        kind,
+        at,
+        estimated: true,
+    });
+    true
+}
+
+fn load_history() -> Result<HashMap<String, Vec<HostingEvent>>> {
+    load_json(&get_data_dir().join("hosting_history.json"))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub enum HostingEventKind {
+    Enabled,
+    Disabled,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct HostingEvent {
+    pub kind: HostingEventKind,
+    pub at: Timestamp,
+    #[serde(default)]
+    pub estimated: bool, // caught up with hha state, the event happened at `at` or earlier
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct HostingHistory {
+    pub happ_id: ActionHashB64,
+    pub first_enabled: Option<Timestamp>, // null when happ has never been enabled
+    pub days_hosted: Option<u16>,         // days since first enabled
+    pub enabled_time: u64,                // seconds happ has been enabled for in total
+    pub events: Vec<HostingEvent>,        // oldest first
+}
+
+impl HostingHistory {
+    fn new(happ_id: ActionHashB64, events: Vec<HostingEvent>, now: Timestamp) -> Self {
+        let first_enabled = events
+            .iter()
+            .find(|event| event.kind == HostingEventKind::Enabled)
+            .map(|event| event.at);
+
+        let mut enabled_micros = 0;
+        let mut enabled_since: Option<Timestamp> = None;
+        for event in events.iter() {
+            match (event.kind, enabled_since) {
+                (HostingEventKind::Enabled, None) => enabled_since = Some(event.at),
+                (HostingEventKind::Disabled, Some(since)) => {
+                    enabled_micros += event.at.as_micros() - since.as_micros();
+                    enabled_since = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(since) = enabled_since {
+            enabled_micros += now.as_micros() - since.as_micros();
+        }
+
+        HostingHistory {
+            happ_id,
+            first_enabled,
+            days_hosted: first_enabled
+                .map(|first| ((now.as_micros() - first.as_micros()) / DAY_MICROS) as u16),
+            enabled_time: (enabled_micros.max(0) / 1_000_000) as u64,
+            events,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use holochain_types::{dna::ActionHashB64, prelude::Timestamp};
+
+    use super::{reconcile, HostingEvent, HostingEventKind, HostingHistory, DAY_MICROS};
+
+    fn event(kind: HostingEventKind, day: i64) -> HostingEvent {
+        HostingEvent {
+            kind,
+            at: Timestamp::from_micros(day * DAY_MICROS),
+            estimated: false,
+        }
+    }
+
+    #[test]
+    fn sums_up_hosting_history() {
+        let id =
+            ActionHashB64::from_b64_str("uhCkklkJVx4u17eCaaKg_phRJsHOj9u57v_4cHQR-Bd9tb-vePRyC")
+                .unwrap();
+        let events = vec![
+            event(HostingEventKind::Enabled, 10),
+            event(HostingEventKind::Disabled, 15),
+            event(HostingEventKind::Enabled, 20),
+        ];
+
+        let history =
+            HostingHistory::new(id.clone(), events, Timestamp::from_micros(30 * DAY_MICROS));
+        assert_eq!(
+            history.first_enabled,
+            Some(Timestamp::from_micros(10 * DAY_MICROS))
+        );
+        assert_eq!(history.days_hosted, Some(20));
+        assert_eq!(history.enabled_time, 15 * 24 * 60 * 60);
+
+        let history = HostingHistory::new(id, vec![], Timestamp::from_micros(30 * DAY_MICROS));
+        assert_eq!(history.first_enabled, None);
+        assert_eq!(history.days_hosted, None);
+        assert_eq!(history.enabled_time, 0);
+    }
+
+    #[test]
+    fn catches_up_with_hha() {
+        let day = |day: i64| Timestamp::from_micros(day * DAY_MICROS);
+        let now = day(30);
+
+        // enabled before history was recorded
+        let mut events = vec![];
+        assert!(reconcile(&mut events, true, Some(day(5)), now));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, HostingEventKind::Enabled);
+        assert_eq!(events[0].at, day(5));
+        assert!(events[0].estimated);
+        assert!(!reconcile(&mut events, true, Some(day(5)), now));
+
+        // disabled and enabled again by other means
+        assert!(reconcile(&mut events, false, Some(day(5)), now));
+        assert_eq!(events[1].kind, HostingEventKind::Disabled);
+        assert_eq!(events[1].at, now);
+        assert!(reconcile(&mut events, true, Some(day(5)), now));
+        assert_eq!(events[2].at, now);
+
+        // never enabled, nothing to catch up with
+        let mut events = vec![];
+        assert!(!reconcile(&mut events, false, None, now));
+        assert!(events.is_empty());
+    }
+}
@@ -5,11 +5,12 @@ use holochain_client::{AgentPubKey, AppInfo};
 use holochain_conductor_api::{AppStatusFilter, CellInfo};
 use holochain_types::app::{AppManifest, InstallAppPayload};
 use holochain_types::dna::{ActionHash, DnaHashB64};
-use holochain_types::prelude::{AppBundleSource, RoleName, YamlProperties};
+use holochain_types::prelude::{AppBundleSource, DnaBundle, RoleName, YamlProperties};
 use hpos_hc_connect::app_connection::CoreAppRoleName;
 use hpos_hc_connect::AppConnection;
 use mr_bundle::Bundle;
 use std::collections::HashMap;
+use std::path::Path;
 
 use super::types::{CellInfoMap, RawInstallAppPayload, SuccessfulInstallResult};
 
@@ -31,12 +32,52 @@ pub async fn handle_holochain_enable(
     }
 }
 
+pub async fn handle_holochain_disable(
+    admin_connection: &mut hpos_hc_connect::AdminWebsocket,
+    installed_app_id: &InstalledAppId,
+) -> Result<()> {
+    match admin_connection.disable_app(installed_app_id).await {
+        Ok(r) => match r {
+            AdminResponse::AppDisabled => Ok(()),
+            _ => Err(anyhow!("Failed to disable installed_app_id {:?}.  Received invalid conductor admin response: {:#?}", installed_app_id, r))
+        },
+        Err(e) => Err(e)
+    }
+}
+
+pub async fn handle_holochain_uninstall(
+    admin_connection: &mut hpos_hc_connect::AdminWebsocket,
+    installed_app_id: &InstalledAppId,
+) -> Result<()> {
+    match admin_connection.uninstall_app(installed_app_id).await {
+        Ok(r) => match r {
+            AdminResponse::AppUninstalled => Ok(()),
+            _ => Err(anyhow!("Failed to uninstall installed_app_id {:?}.  Received invalid conductor admin response: {:#?}", installed_app_id, r))
+        },
+        Err(e) => Err(e)
+    }
+}
+
 pub async fn handle_install_app_raw(
     admin_connection: &mut hpos_hc_connect::AdminWebsocket,
     payload: RawInstallAppPayload,
 ) -> Result<SuccessfulInstallResult> {
     let installed_app_id = payload.installed_app_id.clone();
 
+    // Don't return installation error whenever app is already installed. Conductor reports it
+    // only as a message of an internal error, so installed apps are checked up front instead.
+    let installed_apps = admin_connection
+        .list_apps(None)
+        .await
+        .map_err(|err| anyhow!("{:?}", err))?;
+    if installed_apps
+        .iter()
+        .any(|app| app.installed_app_id == installed_app_id)
+    {
+        log::debug!("App {:?} is already installed", installed_app_id);
+        return Ok(SuccessfulInstallResult::AlreadyInstalled);
+    }
+
     let p = InstallAppPayload {
         ignore_genesis_failure: false,
         source: payload.source,
@@ -44,7 +85,7 @@ pub async fn handle_install_app_raw(
         installed_app_id: Some(payload.installed_app_id),
         membrane_proofs: Some(payload.membrane_proofs),
         network_seed: payload.uid,
-        existing_cells: HashMap::new(),
+        existing_cells: payload.existing_cells,
         allow_throwaway_random_agent_key: false,
     };
     log::trace!("Starting installation of app with bundle: {:?}", p.source);
@@ -56,15 +97,90 @@ pub async fn handle_install_app_raw(
         },
         Err(e) => {
             log::warn!("Warning while installing app {:?} : {:?}", installed_app_id, e);
+            Err(e)
+        }
+    }
+}
 
-            // Don't return installation error whenever app is already installed
-            if !(e.to_string().contains("AppAlreadyInstalled") || e.to_string().contains("CellAlreadyExists")) {
-                return Err(e);
-            }
+/// Checks that DNAs of happ bundle at `bundle_path` are the ones published in hha for `happ`, so that
+/// the code served at `bundle_url` can not be swapped. A DNA matches when its hash, computed either
+/// with modifiers from the bundle or with happ's `uid` applied as network seed at installation,
+/// is one of `happ.dnas[].hash`. Happs that publish no DNA hashes can not be verified.
+pub async fn verify_bundle_dnas(bundle_path: &Path, happ: &PresentedHappBundle) -> Result<()> {
+    if happ.dnas.is_empty() {
+        log::warn!(
+            "Happ {} publishes no DNA hashes in hha, skipping verification of its bundle",
+            happ.id
+        );
+        return Ok(());
+    }
+    let published: Vec<&str> = happ.dnas.iter().map(|dna| dna.hash.as_str()).collect();
 
-            Ok(SuccessfulInstallResult::AlreadyInstalled)
+    let roles = get_bundle_dna_hashes(bundle_path, &happ.uid).await?;
+    match find_unpublished_dna(&roles, &published) {
+        None => Ok(()),
+        Some((role_name, hashes)) => Err(anyhow!(
+            "Failed to verify bundle of happ {}. DNA of role {} has hash {} which is not among DNA hashes published in hha: {}",
+            happ.id,
+            role_name,
+            hashes[hashes.len() - 1],
+            published.join(", ")
+        )),
+    }
+}
+
+/// Returns for each role of happ bundle at `bundle_path` hashes that its DNA can have once installed,
+/// computed with modifiers from the bundle and with `uid` applied as network seed, in this order
+pub async fn get_bundle_dna_hashes(
+    bundle_path: &Path,
+    uid: &Option<String>,
+) -> Result<Vec<(RoleName, Vec<String>)>> {
+    let bundle = Bundle::<AppManifest>::read_from_file(bundle_path).await?;
+    let AppManifest::V1(manifest) = bundle.manifest().clone();
+
+    let mut roles = vec![];
+    for role in manifest.roles {
+        let location = role.dna.location.clone().ok_or_else(|| {
+            anyhow!(
+                "Failed to read bundle {:?}. Role {} has no DNA location",
+                bundle_path,
+                role.name
+            )
+        })?;
+        let dna_bytes = bundle.resolve(&location).await?;
+
+        let bundled_modifiers = role.dna.modifiers.clone().serialized()?;
+        let mut installed_modifiers = bundled_modifiers.clone();
+        if let Some(uid) = uid {
+            installed_modifiers.network_seed = Some(uid.clone());
+        }
+
+        let mut hashes = vec![];
+        for modifiers in [bundled_modifiers, installed_modifiers] {
+            let (_, dna_hash) = DnaBundle::decode(&dna_bytes[..])?
+                .into_dna_file(modifiers)
+                .await?;
+            hashes.push(DnaHashB64::from(dna_hash).to_string());
         }
+        log::debug!(
+            "DNA of role {} in bundle has hashes {:?}",
+            role.name,
+            hashes
+        );
+        roles.push((role.name, hashes));
     }
+
+    Ok(roles)
+}
+
+/// Returns the first role none of whose DNA hashes is in `published`
+pub fn find_unpublished_dna<'a>(
+    roles: &'a [(RoleName, Vec<String>)],
+    published: &[&str],
+) -> Option<&'a (RoleName, Vec<String>)> {
+    roles
+        .iter()
+        .find(|(_, hashes)| !hashes.iter().any(|hash| published.contains(&hash.as_str())))
 }
 
 pub async fn update_happ_bundle(
@@ -127,6 +243,7 @@ pub async fn install_assigned_sl_instance(
         agent_key: host_pub_key,
         installed_app_id: assigned_sl_id,
         membrane_proofs: HashMap::new(), // sl apps do not require mem proofs
+        existing_cells: HashMap::new(),
         uid: None, // sl apps should use the pure `DEV_UID_OVERRIDE` env var as the network id
     };
 
@@ -215,3 +332,38 @@ pub fn get_sl_collector_pubkey() -> String {
     std::env::var("SL_COLLECTOR_PUB_KEY")
         .expect("Failed to read SL_COLLECTOR_PUB_KEY. Is it set in env?")
 }
+
+#[cfg(test)]
+mod test {
+    use super::find_unpublished_dna;
+
+    fn role(name: &str, hashes: &[&str]) -> (String, Vec<String>) {
+        (
+            name.to_string(),
+            hashes.iter().map(|hash| hash.to_string()).collect(),
+        )
+    }
+
+    #[test]
+    fn verifies_bundle_dnas_against_published_hashes() {
+        let published = ["uhC0kA", "uhC0kB"];
+
+        // hash with bundled modifiers or with happ's network seed applied
+        let matching = vec![
+            role("main", &["uhC0kA", "uhC0kX"]),
+            role("other", &["uhC0kY", "uhC0kB"]),
+        ];
+        assert!(find_unpublished_dna(&matching, &published).is_none());
+
+        let mismatching = vec![
+            role("main", &["uhC0kA", "uhC0kX"]),
+            role("other", &["uhC0kY", "uhC0kZ"]),
+        ];
+        let (role_name, hashes) = find_unpublished_dna(&mismatching, &published).unwrap();
+        assert_eq!(role_name, "other");
+        assert_eq!(hashes, &vec!["uhC0kY".to_string(), "uhC0kZ".to_string()]);
+
+        // bundle without DNAs has nothing to mismatch
+        assert!(find_unpublished_dna(&[], &published).is_none());
+    }
+}
@@ -0,0 +1,285 @@
+// Bookkeeping of installations running in the background. Jobs live in memory only,
+// so they are lost on restart of the API together with the installations they were running.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use holochain_types::prelude::Timestamp;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio::sync::Mutex;
+
+/// Max number of finished jobs kept for inspection, the oldest ones are dropped first
+const MAX_FINISHED_JOBS: usize = 100;
+
+pub type InstallJobsMutex = Arc<Mutex<InstallJobs>>;
+
+#[derive(Default)]
+pub struct InstallJobs {
+    last_id: u64,
+    jobs: BTreeMap<u64, InstallJob>,
+}
+
+impl InstallJobs {
+    pub fn new() -> InstallJobsMutex {
+        Arc::new(Mutex::new(InstallJobs::default()))
+    }
+
+    /// Creates a job installing `happ_id`, unless such a job is already running.
+    /// Returns the job and whether it has been created, running job is returned as is.
+    pub fn create(&mut self, happ_id: &str) -> (InstallJob, bool) {
+        if let Some(job) = self
+            .jobs
+            .values()
+            .find(|job| job.happ_id == happ_id && job.state == JobState::Running)
+        {
+            return (job.clone(), false);
+        }
+
+        self.last_id += 1;
+        let job = InstallJob {
+            id: self.last_id,
+            happ_id: happ_id.to_string(),
+            state: JobState::Running,
+            phase: InstallPhase::FetchDetails,
+            progress: InstallPhase::FetchDetails.progress(),
+            error: None,
+            cancel_requested: false,
+            created_at: Timestamp::now(),
+            finished_at: None,
+        };
+        self.jobs.insert(job.id, job.clone());
+        self.prune();
+        (job, true)
+    }
+
+    pub fn get(&self, id: u64) -> Option<&InstallJob> {
+        self.jobs.get(&id)
+    }
+
+    /// Returns all jobs, newest first
+    pub fn list(&self) -> Vec<InstallJob> {
+        self.jobs.values().rev().cloned().collect()
+    }
+
+    /// Marks job as to be cancelled, the job stops before entering its next phase.
+    /// Job stays running until apps installed by earlier phases are uninstalled again.
+    pub fn cancel(&mut self, id: u64) -> Result<InstallJob> {
+        let job = self
+            .jobs
+            .get_mut(&id)
+            .ok_or_else(|| anyhow!("Install job {} does not exist", id))?;
+        if job.state != JobState::Running {
+            return Err(anyhow!("Install job {} has already finished", id));
+        }
+        job.cancel_requested = true;
+        Ok(job.clone())
+    }
+
+    fn prune(&mut self) {
+        let finished: Vec<u64> = self
+            .jobs
+            .values()
+            .filter(|job| job.state != JobState::Running)
+            .map(|job| job.id)
+            .collect();
+        for id in finished
+            .iter()
+            .take(finished.len().saturating_sub(MAX_FINISHED_JOBS))
+        {
+            self.jobs.remove(id);
+        }
+    }
+}
+
+/// Handle through which a running installation reports its progress.
+/// Installations that are not run as a job report to nobody.
+#[derive(Clone, Default)]
+pub struct JobProgress {
+    job: Option<(u64, InstallJobsMutex)>,
+}
+
+impl JobProgress {
+    pub fn new(id: u64, jobs: InstallJobsMutex) -> Self {
+        JobProgress {
+            job: Some((id, jobs)),
+        }
+    }
+
+    /// Records that installation has entered `phase`.
+    /// Fails when cancellation of the job has been requested, so that installation stops here.
+    /// Job is marked as cancelled by `finish`, after installation has been rolled back.
+    pub async fn enter(&self, phase: InstallPhase) -> Result<()> {
+        if let Some((id, jobs)) = &self.job {
+            let mut jobs = jobs.lock().await;
+            if let Some(job) = jobs.jobs.get_mut(id) {
+                if job.cancel_requested {
+                    return Err(anyhow!(
+                        "Installation of happ {} was cancelled before {:?}",
+                        job.happ_id,
+                        phase
+                    ));
+                }
+                job.phase = phase;
+                job.progress = phase.progress();
+            }
+        }
+        Ok(())
+    }
+
+    /// Records the outcome of installation, once it has been rolled back in case of failure.
+    /// Installation that has failed after cancellation was requested is recorded as cancelled.
+    pub async fn finish(&self, result: &Result<String>) {
+        if let Some((id, jobs)) = &self.job {
+            let mut jobs = jobs.lock().await;
+            if let Some(job) = jobs.jobs.get_mut(id) {
+                if job.state == JobState::Running {
+                    match result {
+                        Ok(_) => {
+                            job.state = JobState::Completed;
+                            job.progress = 100;
+                        }
+                        Err(e) => {
+                            job.state = if job.cancel_requested {
+                                JobState::Cancelled
+                            } else {
+                                JobState::Failed
+                            };
+                            job.error = Some(e.to_string());
+                        }
+                    }
+                    job.finished_at = Some(Timestamp::now());
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct InstallJob {
+    pub id: u64,
+    pub happ_id: String,
+    pub state: JobState,
+    pub phase: InstallPhase, // phase being run, or the last one that was run if job has finished
+    pub progress: u8,        // percentage
+    pub error: Option<String>,
+    pub cancel_requested: bool,
+    pub created_at: Timestamp,
+    pub finished_at: Option<Timestamp>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub enum JobState {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// Phases of installation in the order they are run. Phases that install and enable
+/// happ's instance are skipped for happs with `special_installed_app_id`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub enum InstallPhase {
+    FetchDetails,
+    DownloadSl,
+    InstallSl,
+    EnableSl,
+    DownloadHapp,
+    InstallHapp,
+    EnableHapp,
+    EnableInHha,
+}
+
+impl InstallPhase {
+    /// Percentage of installation completed when the phase starts,
+    /// downloads and installs take more time than the rest
+    pub fn progress(&self) -> u8 {
+        match self {
+            InstallPhase::FetchDetails => 0,
+            InstallPhase::DownloadSl => 5,
+            InstallPhase::InstallSl => 20,
+            InstallPhase::EnableSl => 35,
+            InstallPhase::DownloadHapp => 40,
+            InstallPhase::InstallHapp => 65,
+            InstallPhase::EnableHapp => 85,
+            InstallPhase::EnableInHha => 90,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::anyhow;
+
+    use super::*;
+
+    #[rocket::async_test]
+    async fn tracks_job_progress() {
+        let jobs = InstallJobs::new();
+        let (job, _) = jobs.lock().await.create("happ");
+        let progress = JobProgress::new(job.id, jobs.clone());
+
+        progress.enter(InstallPhase::InstallSl).await.unwrap();
+        let job = jobs.lock().await.get(job.id).unwrap().clone();
+        assert_eq!(job.phase, InstallPhase::InstallSl);
+        assert_eq!(job.progress, 20);
+
+        progress.finish(&Ok("done".to_string())).await;
+        let job = jobs.lock().await.get(job.id).unwrap().clone();
+        assert_eq!(job.state, JobState::Completed);
+        assert_eq!(job.progress, 100);
+        assert!(jobs.lock().await.cancel(job.id).is_err());
+    }
+
+    #[rocket::async_test]
+    async fn cancels_job_before_next_phase() {
+        let jobs = InstallJobs::new();
+        let (job, _) = jobs.lock().await.create("happ");
+        let progress = JobProgress::new(job.id, jobs.clone());
+
+        jobs.lock().await.cancel(job.id).unwrap();
+        assert!(progress.enter(InstallPhase::DownloadSl).await.is_err());
+
+        // job keeps running while installation is rolled back, so it can not be started again meanwhile
+        let running = jobs.lock().await.get(job.id).unwrap().clone();
+        assert_eq!(running.state, JobState::Running);
+        assert!(running.finished_at.is_none());
+        assert!(!jobs.lock().await.create("happ").1);
+
+        progress.finish(&Err(anyhow!("cancelled"))).await;
+        let job = jobs.lock().await.get(job.id).unwrap().clone();
+        assert_eq!(job.state, JobState::Cancelled);
+        assert_eq!(job.phase, InstallPhase::FetchDetails);
+        assert!(job.finished_at.is_some());
+    }
+
+    #[rocket::async_test]
+    async fn returns_running_job_of_same_happ() {
+        let jobs = InstallJobs::new();
+        let (job, created) = jobs.lock().await.create("happ");
+        assert!(created);
+
+        let (same, created) = jobs.lock().await.create("happ");
+        assert!(!created);
+        assert_eq!(same.id, job.id);
+
+        let (other, created) = jobs.lock().await.create("other");
+        assert!(created);
+        assert_ne!(other.id, job.id);
+
+        // once the job has finished happ can be installed again
+        JobProgress::new(job.id, jobs.clone())
+            .finish(&Err(anyhow!("failed")))
+            .await;
+        let (retry, created) = jobs.lock().await.create("happ");
+        assert!(created);
+        assert_ne!(retry.id, job.id);
+        assert_eq!(jobs.lock().await.list().len(), 3);
+    }
+}
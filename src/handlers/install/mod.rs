@@ -1,8 +1,9 @@
 /*
 Hosted Happ Installation Overview
 
-Steps to install a happ bundle:
-- Download the bundle to a particular location.
+Steps to install a happ bundle (apps installed by a step are uninstalled again when a later step fails):
+- Download the bundle to a particular location (or reuse its copy from the bundle cache).
+    - Verify that DNA hashes of the bundle match those published in hha
     - Use that location and install
 - Install a servicelogger instance for hosted happ
     - We need to know the path to the base servicelogger
@@ -15,116 +16,241 @@ Steps to install a happ bundle:
 */
 
 mod helpers;
+mod jobs;
+mod steps;
 mod types;
 
+use std::collections::HashMap;
+
 use anyhow::{anyhow, Result};
 use url::Url;
 
+use super::bundles;
 use super::hosted_happs::handle_enable;
-use crate::common::types::PresentedHappBundle;
-use crate::hpos::Ws;
-pub use helpers::update_happ_bundle;
+use crate::common::types::{HappAndHost, PresentedHappBundle};
+use crate::hpos::WsMutex;
+pub use helpers::{
+    find_unpublished_dna, get_app_details, get_base_dna_hash, get_bundle_dna_hashes,
+    get_host_pub_key, get_sl_id, handle_holochain_disable, handle_holochain_enable,
+    handle_holochain_uninstall, handle_install_app_raw, update_happ_bundle, verify_bundle_dnas,
+};
 use holochain_types::dna::ActionHashB64;
 use holochain_types::prelude::AppBundleSource;
+use hpos_hc_connect::holo_config::Happ;
+pub use jobs::{InstallJob, InstallJobs, InstallJobsMutex, InstallPhase, JobProgress, JobState};
+use steps::{Compensation, InstallSteps};
 pub use types::*;
 
-pub async fn handle_install_app(ws: &mut Ws, data: types::InstallHappBody) -> Result<String> {
+/// Installs hosted happ together with its servicelogger instance and enables it in hha.
+/// Lock on `wsm` is held for each step separately and released for downloads,
+/// so that other requests can be served while a long installation is running.
+/// Progress is reported through `progress`, which also stops installation when the job is cancelled.
+/// When a step fails or the job is cancelled, apps installed by earlier steps are uninstalled again.
+pub async fn handle_install_app(
+    wsm: &WsMutex,
+    data: types::InstallHappBody,
+    progress: &JobProgress,
+) -> Result<String> {
+    let mut steps = InstallSteps::new(&data.happ_id, progress);
+    let result = install_app(wsm, data, &mut steps).await;
+    let result = match result {
+        Ok(result) => Ok(result),
+        Err(e) => Err(steps.rollback(wsm, e).await),
+    };
+    progress.finish(&result).await;
+    result
+}
+
+async fn install_app(
+    wsm: &WsMutex,
+    data: types::InstallHappBody,
+    steps: &mut InstallSteps<'_>,
+) -> Result<String> {
     log::debug!("Calling zome hosted/install with payload: {:?}", &data);
-    let maybe_pubkey = ws.host_pub_key.clone();
-    let base_sl = ws.base_sl.clone();
-    let mut admin_connection = ws.admin.clone();
-    let core_app_connection = ws.get_connection(ws.core_app_id.clone()).await?;
+    steps.enter(InstallPhase::FetchDetails).await?;
+    let (host_pub_key, happ_bundle_details, base_sl, core_happ_cell_info, already_installed) = {
+        let mut ws = wsm.lock().await;
+        let maybe_pubkey = ws.host_pub_key.clone();
+        let base_sl = ws.base_sl.clone();
+        let mut admin_connection = ws.admin.clone();
+        let core_app_connection = ws.get_connection(ws.core_app_id.clone()).await?;
 
-    // Note: We will be installing the hosted happ and their associated sl cells with the host pubkey
-    let host_pub_key = helpers::get_host_pub_key(maybe_pubkey, core_app_connection).await?;
+        // Note: We will be installing the hosted happ and their associated sl cells with the host pubkey
+        let host_pub_key = helpers::get_host_pub_key(maybe_pubkey, core_app_connection).await?;
 
-    let happ_bundle_details: PresentedHappBundle = helpers::get_app_details(
-        core_app_connection,
-        ActionHashB64::from_b64_str(&data.happ_id)?.into(),
-    )
-    .await?;
+        let happ_bundle_details: PresentedHappBundle = helpers::get_app_details(
+            core_app_connection,
+            ActionHashB64::from_b64_str(&data.happ_id)?.into(),
+        )
+        .await?;
+
+        let core_happ_cell_info = core_app_connection.app_info().await?.cell_info;
+
+        let already_installed = helpers::is_already_installed(
+            &mut admin_connection,
+            happ_bundle_details.id.to_string(),
+        )
+        .await?;
+
+        (
+            host_pub_key,
+            happ_bundle_details,
+            base_sl,
+            core_happ_cell_info,
+            already_installed,
+        )
+    };
+
+    if !already_installed {
+        // NB: If the happ has not yet been installed, we must take 4 steps: 1. install app's sl, 2. enable app's sl, 3. install app, 4. enable app
+        // 1. Install the sl instance assigned to the hosted happ
+        // Download the servicelogger source code for sl happ instance install
+        steps.enter(InstallPhase::DownloadSl).await?;
+        let bundle_url = get_sl_bundle_url(base_sl, &data.happ_id)?;
+
+        log::debug!("Downloading bundle URL...{:?}", bundle_url);
+        let sl_bundle = bundles::get_bundle(&bundle_url, false).await?;
 
-    match helpers::is_already_installed(&mut admin_connection, happ_bundle_details.id.to_string())
+        steps.enter(InstallPhase::InstallSl).await?;
+        let sl_app_id = match helpers::install_assigned_sl_instance(
+            &mut wsm.lock().await.admin,
+            &data.happ_id,
+            host_pub_key.to_owned(),
+            &core_happ_cell_info,
+            AppBundleSource::Path(sl_bundle.path.clone()),
+        )
         .await?
-    {
-        true => {
-            // NB: If app is already installed, then we only need to make the happ as enable in hha.
-            handle_enable(ws, &data.happ_id).await?;
-        }
-        false => {
-            // NB: If the happ has not yet been installed, we must take 4 steps: 1. install app's sl, 2. enable app's sl, 3. install app, 4. enable app
-            // 1. Install the sl instance assigned to the hosted happ
-            // Download the servicelogger source code for sl happ instance install
-            let bundle_url = match base_sl.bundle_url {
-                Some(url) => url,
-                None => match base_sl.bundle_path {
-                    Some(path) => match Url::from_file_path(path) {
-                        Ok(url) => url,
-                        Err(e) => return Err(anyhow!(
-                            "Failed to install happ with `happ_id`: {:?}. Unable to read source code url for servicelogger.  Error: {:?}", data.happ_id, e
-                        ))
-                },
-                    None => return Err(anyhow!(
-                        "Failed to install happ with `happ_id`: {:?}. Unable to locate source code url for servicelogger.", data.happ_id
-                    ))
-                }
-            };
+        {
+            SuccessfulInstallResult::New(a) => {
+                steps.compensate(Compensation::Uninstall(a.installed_app_id.clone()));
+                a.installed_app_id
+            }
+            SuccessfulInstallResult::AlreadyInstalled => helpers::get_sl_id(&data.happ_id),
+        };
 
-            let core_happ_cell_info = core_app_connection.app_info().await?.cell_info;
+        // 2. Enable the sl instance assigned to the hosted happ
+        steps.enter(InstallPhase::EnableSl).await?;
+        helpers::handle_holochain_enable(&mut wsm.lock().await.admin, &sl_app_id).await?;
 
+        // Steps 3 & 4 are only for non-core hosted apps (ie: whenever the app does not have the `special_installed_app_id` property)
+        if happ_bundle_details.special_installed_app_id.is_none() {
+            // 3. Install the hosted happ
+            // Download the app source code to install
+            steps.enter(InstallPhase::DownloadHapp).await?;
             log::debug!(
                 "Downloading bundle URL...{:?}",
                 happ_bundle_details.bundle_url
             );
-            let sl_bundle_path = hpos_hc_connect::utils::download_file(&bundle_url).await?;
-
-            let sl_app_id = match helpers::install_assigned_sl_instance(
-                &mut admin_connection,
-                &data.happ_id,
-                host_pub_key.to_owned(),
-                &core_happ_cell_info,
-                AppBundleSource::Path(sl_bundle_path),
-            )
-            .await?
-            {
-                SuccessfulInstallResult::New(a) => a.installed_app_id,
-                SuccessfulInstallResult::AlreadyInstalled => helpers::get_sl_id(&data.happ_id),
+            let happ_bundle = get_verified_bundle(&happ_bundle_details).await?;
+
+            // Install app
+            steps.enter(InstallPhase::InstallHapp).await?;
+            let raw_payload = types::RawInstallAppPayload {
+                source: AppBundleSource::Path(happ_bundle.path.clone()),
+                agent_key: host_pub_key.to_owned(),
+                installed_app_id: happ_bundle_details.id.to_string(),
+                membrane_proofs: data.membrane_proofs.clone(),
+                existing_cells: HashMap::new(),
+                uid: happ_bundle_details.uid,
             };
 
-            // 2. Enable the sl instance assigned to the hosted happ
-            helpers::handle_holochain_enable(&mut admin_connection, &sl_app_id).await?;
-
-            // Steps 3 & 4 are only for non-core hosted apps (ie: whenever the app does not have the `special_installed_app_id` property)
-            if happ_bundle_details.special_installed_app_id.is_none() {
-                // 3. Install the hosted happ
-                // Download the app source code to install
-                let bundle_url = Url::parse(&happ_bundle_details.bundle_url)?;
-                log::debug!(
-                    "Downloading bundle URL...{:?}",
-                    happ_bundle_details.bundle_url
-                );
-                let happ_bundle_path = hpos_hc_connect::utils::download_file(&bundle_url).await?;
-
-                // Install app
-                let raw_payload = types::RawInstallAppPayload {
-                    source: AppBundleSource::Path(happ_bundle_path),
-                    agent_key: host_pub_key.to_owned(),
-                    installed_app_id: happ_bundle_details.id.to_string(),
-                    membrane_proofs: data.membrane_proofs,
-                    uid: happ_bundle_details.uid,
-                };
-
-                helpers::handle_install_app_raw(&mut admin_connection, raw_payload).await?;
-
-                // 4. Enable the hosted happ
-                helpers::handle_holochain_enable(&mut admin_connection, &data.happ_id).await?;
+            if let SuccessfulInstallResult::New(a) =
+                helpers::handle_install_app_raw(&mut wsm.lock().await.admin, raw_payload).await?
+            {
+                steps.compensate(Compensation::Uninstall(a.installed_app_id));
+                // upgrade rolls back to this bundle and reinstalls with the same proofs
+                bundles::record_install(
+                    &data.happ_id,
+                    &happ_bundle_details.bundle_url,
+                    &happ_bundle,
+                    data.membrane_proofs,
+                )?;
             }
-            handle_enable(ws, &data.happ_id).await?;
+
+            // 4. Enable the hosted happ
+            steps.enter(InstallPhase::EnableHapp).await?;
+            helpers::handle_holochain_enable(&mut wsm.lock().await.admin, &data.happ_id).await?;
         }
     }
 
+    // NB: If app is already installed, then we only need to make the happ as enable in hha.
+    steps.enter(InstallPhase::EnableInHha).await?;
+    let payload = HappAndHost::init(&data.happ_id).await?;
+    handle_enable(&mut *wsm.lock().await, payload).await?;
+
     Ok(format!(
         "Successfully installed happ_id: {:?}",
         data.happ_id
     ))
 }
+
+/// Installs and enables servicelogger instance of hosted happ `happ_id`.
+/// Happ's own instance is left untouched, so a happ that is serving traffic keeps running whatever happens here.
+pub async fn handle_install_servicelogger(wsm: &WsMutex, happ_id: &str) -> Result<String> {
+    let (host_pub_key, base_sl, core_happ_cell_info) = {
+        let mut ws = wsm.lock().await;
+        let maybe_pubkey = ws.host_pub_key.clone();
+        let base_sl = ws.base_sl.clone();
+        let core_app_connection = ws.get_connection(ws.core_app_id.clone()).await?;
+        let host_pub_key = helpers::get_host_pub_key(maybe_pubkey, core_app_connection).await?;
+        let core_happ_cell_info = core_app_connection.app_info().await?.cell_info;
+        (host_pub_key, base_sl, core_happ_cell_info)
+    };
+
+    let bundle_url = get_sl_bundle_url(base_sl, happ_id)?;
+    log::debug!("Downloading bundle URL...{:?}", bundle_url);
+    let sl_bundle = bundles::get_bundle(&bundle_url, false).await?;
+
+    let mut ws = wsm.lock().await;
+    let sl_app_id = match helpers::install_assigned_sl_instance(
+        &mut ws.admin,
+        &happ_id.to_string(),
+        host_pub_key,
+        &core_happ_cell_info,
+        AppBundleSource::Path(sl_bundle.path.clone()),
+    )
+    .await?
+    {
+        SuccessfulInstallResult::New(a) => a.installed_app_id,
+        SuccessfulInstallResult::AlreadyInstalled => helpers::get_sl_id(&happ_id.to_string()),
+    };
+    helpers::handle_holochain_enable(&mut ws.admin, &sl_app_id).await?;
+
+    Ok(sl_app_id)
+}
+
+/// Returns url of the base servicelogger bundle that servicelogger instances of hosted happs are installed from
+fn get_sl_bundle_url(base_sl: Happ, happ_id: &str) -> Result<Url> {
+    match base_sl.bundle_url {
+        Some(url) => Ok(url),
+        None => match base_sl.bundle_path {
+            Some(path) => Url::from_file_path(path).map_err(|e| anyhow!(
+                "Failed to install happ with `happ_id`: {:?}. Unable to read source code url for servicelogger.  Error: {:?}", happ_id, e
+            )),
+            None => Err(anyhow!(
+                "Failed to install happ with `happ_id`: {:?}. Unable to locate source code url for servicelogger.", happ_id
+            )),
+        },
+    }
+}
+
+/// Returns bundle of `happ` with DNAs verified against those published in hha. Cached copy of the bundle
+/// may be content that the publisher has since replaced at the same url, so when it does not match,
+/// the bundle is downloaded again before giving up.
+pub async fn get_verified_bundle(happ: &PresentedHappBundle) -> Result<bundles::BundleFile> {
+    let bundle_url = Url::parse(&happ.bundle_url)?;
+    let bundle = bundles::get_bundle(&bundle_url, false).await?;
+    match helpers::verify_bundle_dnas(&bundle.path, happ).await {
+        Ok(()) => Ok(bundle),
+        Err(e) => {
+            log::warn!(
+                "Bundle of happ {} does not match DNAs published in hha, downloading it again: {}",
+                happ.id,
+                e
+            );
+            let bundle = bundles::get_bundle(&bundle_url, true).await?;
+            helpers::verify_bundle_dnas(&bundle.path, happ).await?;
+            Ok(bundle)
+        }
+    }
+}
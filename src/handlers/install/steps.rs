@@ -0,0 +1,206 @@
+// Installation is run as a sequence of steps (`InstallPhase`). Steps that create something in holochain
+// register a compensation undoing it, and when a later step fails compensations are run in reverse order,
+// so that a failed installation does not leave e.g. an orphaned servicelogger instance behind.
+// Apps that were already installed before the installation started are never touched.
+
+use std::future::Future;
+
+use anyhow::{anyhow, Error, Result};
+use log::{debug, warn};
+
+use super::helpers::handle_holochain_uninstall;
+use super::jobs::{InstallPhase, JobProgress};
+use crate::handlers::bundles;
+use crate::hpos::WsMutex;
+
+pub struct InstallSteps<'a> {
+    happ_id: String,
+    progress: &'a JobProgress,
+    current: InstallPhase,
+    compensations: Vec<Compensation>,
+}
+
+impl<'a> InstallSteps<'a> {
+    pub fn new(happ_id: &str, progress: &'a JobProgress) -> Self {
+        InstallSteps {
+            happ_id: happ_id.to_string(),
+            progress,
+            current: InstallPhase::FetchDetails,
+            compensations: vec![],
+        }
+    }
+
+    /// Starts step `phase`, fails when the installation job has been cancelled
+    pub async fn enter(&mut self, phase: InstallPhase) -> Result<()> {
+        self.current = phase;
+        self.progress.enter(phase).await
+    }
+
+    /// Registers an action undoing what the current step has created
+    pub fn compensate(&mut self, compensation: Compensation) {
+        self.compensations.push(compensation);
+    }
+
+    /// Undoes completed steps after installation has failed with `error`.
+    /// Returned error names the step that failed and lists what has been rolled back.
+    pub async fn rollback(self, wsm: &WsMutex, error: Error) -> Error {
+        self.rollback_with(error, move |compensation| async move {
+            compensation.run(wsm).await
+        })
+        .await
+    }
+
+    /// Runs compensations with `run`, most recent first
+    async fn rollback_with<F, Fut>(self, error: Error, mut run: F) -> Error
+    where
+        F: FnMut(Compensation) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let mut rolled_back = vec![];
+        let mut rollback_errors = vec![];
+
+        for compensation in self.compensations.iter().rev() {
+            debug!(
+                "rolling back installation of happ {}: {}",
+                self.happ_id, compensation
+            );
+            match run(compensation.clone()).await {
+                Ok(()) => rolled_back.push(compensation.to_string()),
+                Err(e) => {
+                    warn!(
+                        "Failed to roll back installation of happ {} ({}): {}",
+                        self.happ_id, compensation, e
+                    );
+                    rollback_errors.push(format!("{}: {}", compensation, e));
+                }
+            }
+        }
+
+        step_error(
+            &self.happ_id,
+            self.current,
+            error,
+            &rolled_back,
+            &rollback_errors,
+        )
+    }
+}
+
+/// Action undoing a completed step of installation
+#[derive(Clone)]
+pub enum Compensation {
+    Uninstall(String), // installed_app_id
+}
+
+impl Compensation {
+    async fn run(&self, wsm: &WsMutex) -> Result<()> {
+        match self {
+            Compensation::Uninstall(installed_app_id) => {
+                let mut ws = wsm.lock().await;
+                handle_holochain_uninstall(&mut ws.admin, installed_app_id).await?;
+                ws.apps.remove(installed_app_id);
+                bundles::forget_install(installed_app_id)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Compensation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Compensation::Uninstall(installed_app_id) => {
+                write!(f, "uninstall {}", installed_app_id)
+            }
+        }
+    }
+}
+
+fn step_error(
+    happ_id: &str,
+    step: InstallPhase,
+    error: Error,
+    rolled_back: &[String],
+    rollback_errors: &[String],
+) -> Error {
+    let mut message = format!(
+        "Installation of happ {} failed at step {:?}: {}",
+        happ_id, step, error
+    );
+    if !rolled_back.is_empty() {
+        message.push_str(&format!(". Rolled back: {}", rolled_back.join(", ")));
+    }
+    if !rollback_errors.is_empty() {
+        message.push_str(&format!(
+            ". Failed to roll back: {}",
+            rollback_errors.join(", ")
+        ));
+    }
+    anyhow!(message)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use anyhow::anyhow;
+
+    use super::{step_error, Compensation, InstallPhase, InstallSteps};
+    use crate::handlers::install::{InstallJobs, JobProgress};
+
+    #[rocket::async_test]
+    async fn runs_compensations_when_later_step_fails() {
+        let jobs = InstallJobs::new();
+        let (job, _) = jobs.lock().await.create("uhCkk");
+        let progress = JobProgress::new(job.id, jobs.clone());
+        let mut steps = InstallSteps::new("uhCkk", &progress);
+
+        steps.enter(InstallPhase::InstallSl).await.unwrap();
+        steps.compensate(Compensation::Uninstall("uhCkk::servicelogger".to_string()));
+        steps.enter(InstallPhase::EnableSl).await.unwrap();
+        steps.enter(InstallPhase::InstallHapp).await.unwrap();
+
+        let ran = Arc::new(Mutex::new(vec![]));
+        let error = steps
+            .rollback_with(anyhow!("bad bundle"), |compensation| {
+                let ran = ran.clone();
+                async move {
+                    ran.lock().unwrap().push(compensation.to_string());
+                    Ok(())
+                }
+            })
+            .await;
+
+        assert_eq!(*ran.lock().unwrap(), vec!["uninstall uhCkk::servicelogger"]);
+        assert_eq!(
+            error.to_string(),
+            "Installation of happ uhCkk failed at step InstallHapp: bad bundle. Rolled back: uninstall uhCkk::servicelogger"
+        );
+    }
+
+    #[test]
+    fn names_failed_step_and_rollback() {
+        let error = step_error(
+            "uhCkk",
+            InstallPhase::InstallHapp,
+            anyhow!("bad bundle"),
+            &[Compensation::Uninstall("uhCkk::servicelogger".to_string()).to_string()],
+            &[],
+        );
+        assert_eq!(
+            error.to_string(),
+            "Installation of happ uhCkk failed at step InstallHapp: bad bundle. Rolled back: uninstall uhCkk::servicelogger"
+        );
+
+        let error = step_error(
+            "uhCkk",
+            InstallPhase::EnableSl,
+            anyhow!("oops"),
+            &[],
+            &["uninstall uhCkk::servicelogger: conductor is down".to_string()],
+        );
+        assert_eq!(
+            error.to_string(),
+            "Installation of happ uhCkk failed at step EnableSl: oops. Failed to roll back: uninstall uhCkk::servicelogger: conductor is down"
+        );
+    }
+}
@@ -5,7 +5,7 @@ use holochain_conductor_api::CellInfo;
 use holochain_types::{
     app::AppBundleSource,
     dna::AgentPubKey,
-    prelude::{MembraneProof, RoleName},
+    prelude::{CellId, MembraneProof, RoleName},
 };
 
 use holofuel_types::fuel::Fuel;
@@ -33,6 +33,7 @@ pub struct RawInstallAppPayload {
     pub agent_key: AgentPubKey,
     pub installed_app_id: String,
     pub membrane_proofs: HashMap<RoleName, MembraneProof>,
+    pub existing_cells: HashMap<RoleName, CellId>, // cells of other apps used by roles provisioned with `UseExisting`
     pub uid: Option<String>,
 }
 
@@ -0,0 +1,243 @@
+// Inventory of apps installed in the conductor, as holochain sees them. Hosted happs are installed under
+// naming conventions that tie each app back to the happ in hha it belongs to:
+// - `<happ_id>` - happ's instance under host's agent
+// - `<happ_id>:<agent_pubkey>` - instance of a hosted web agent
+// - `<happ_id>::servicelogger` - happ's servicelogger instance
+
+use anyhow::{anyhow, Result};
+use holochain_client::AppInfo;
+use holochain_conductor_api::{AppInfoStatus, AppStatusFilter, CellInfo};
+use holochain_types::{
+    dna::{ActionHashB64, AgentPubKeyB64, DnaHashB64},
+    prelude::Timestamp,
+};
+use rocket::{
+    serde::{Deserialize, Serialize},
+    FromFormField,
+};
+
+use crate::hpos::Ws;
+
+/// Lists apps installed in the conductor, only those with `status` when it is provided
+pub async fn handle_get_installed(
+    ws: &mut Ws,
+    status: Option<AppStatus>,
+) -> Result<Vec<InstalledApp>> {
+    let core_app_id = ws.core_app_id.clone();
+    let apps = ws
+        .admin
+        .list_apps(status.map(AppStatusFilter::from))
+        .await
+        .map_err(|err| anyhow!("{:?}", err))?;
+
+    let mut result: Vec<InstalledApp> = apps
+        .into_iter()
+        .map(|info| InstalledApp::new(info, &core_app_id))
+        .collect();
+    result.sort_by(|a, b| a.installed_app_id.cmp(&b.installed_app_id));
+
+    Ok(result)
+}
+
+/// Tells what `installed_app_id` is an instance of, from the naming conventions of hosted happs
+fn parse_instance(
+    installed_app_id: &str,
+    core_app_id: &str,
+) -> (InstanceKind, Option<ActionHashB64>) {
+    if installed_app_id == core_app_id {
+        return (InstanceKind::Core, None);
+    }
+    if let Some(happ_id) = installed_app_id.strip_suffix("::servicelogger") {
+        if let Ok(happ_id) = ActionHashB64::from_b64_str(happ_id) {
+            return (InstanceKind::Servicelogger, Some(happ_id));
+        }
+    }
+    if let Some((happ_id, agent)) = installed_app_id.split_once(':') {
+        if let (Ok(happ_id), Ok(_)) = (
+            ActionHashB64::from_b64_str(happ_id),
+            AgentPubKeyB64::from_b64_str(agent),
+        ) {
+            return (InstanceKind::Agent, Some(happ_id));
+        }
+    }
+    if let Ok(happ_id) = ActionHashB64::from_b64_str(installed_app_id) {
+        return (InstanceKind::Happ, Some(happ_id));
+    }
+    (InstanceKind::Other, None)
+}
+
+/// Groups cells of an app by role, roles ordered by name
+fn get_roles(info: &AppInfo) -> Vec<InstalledRole> {
+    let mut roles: Vec<InstalledRole> = info
+        .cell_info
+        .iter()
+        .map(|(role_name, cells)| {
+            let mut role = InstalledRole {
+                role_name: role_name.clone(),
+                dna_hash: None,
+                agent: None,
+                clones: vec![],
+            };
+            for cell in cells {
+                match cell {
+                    CellInfo::Provisioned(cell) => {
+                        role.dna_hash = Some(cell.cell_id.dna_hash().clone().into());
+                        role.agent = Some(cell.cell_id.agent_pubkey().clone().into());
+                    }
+                    CellInfo::Cloned(cell) => role.clones.push(CloneCell {
+                        clone_id: cell.clone_id.to_string(),
+                        name: cell.name.clone(),
+                        dna_hash: cell.cell_id.dna_hash().clone().into(),
+                        agent: cell.cell_id.agent_pubkey().clone().into(),
+                        enabled: cell.enabled,
+                    }),
+                    // Stem cells have not been instantiated yet, so they run under no agent
+                    CellInfo::Stem(cell) => {
+                        role.dna_hash = Some(cell.original_dna_hash.clone().into());
+                    }
+                }
+            }
+            role
+        })
+        .collect();
+    roles.sort_by(|a, b| a.role_name.cmp(&b.role_name));
+    roles
+}
+
+/// Reason why app is not running, as reported by the conductor
+fn get_disabled_reason(status: &AppInfoStatus) -> Option<String> {
+    match status {
+        AppInfoStatus::Disabled { reason } => Some(format!("{:?}", reason)),
+        AppInfoStatus::Paused { reason } => Some(format!("{:?}", reason)),
+        _ => None,
+    }
+}
+
+#[derive(Serialize, Deserialize, FromFormField, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub enum AppStatus {
+    Enabled,
+    Disabled,
+    Running,
+    Stopped,
+    Paused,
+}
+
+impl From<AppStatus> for AppStatusFilter {
+    fn from(status: AppStatus) -> Self {
+        match status {
+            AppStatus::Enabled => AppStatusFilter::Enabled,
+            AppStatus::Disabled => AppStatusFilter::Disabled,
+            AppStatus::Running => AppStatusFilter::Running,
+            AppStatus::Stopped => AppStatusFilter::Stopped,
+            AppStatus::Paused => AppStatusFilter::Paused,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub enum InstanceKind {
+    Core,          // core app (hha and holofuel)
+    Happ,          // `<happ_id>`
+    Agent,         // `<happ_id>:<agent_pubkey>`
+    Servicelogger, // `<happ_id>::servicelogger`
+    Other,         // e.g. a self-hosted happ
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct InstalledApp {
+    pub installed_app_id: String,
+    pub status: AppInfoStatus,
+    pub disabled_reason: Option<String>,
+    pub agent: AgentPubKeyB64,
+    pub installed_at: Timestamp,
+    pub kind: InstanceKind,
+    pub hosted_happ_id: Option<ActionHashB64>, // null for apps that do not belong to a hosted happ
+    pub roles: Vec<InstalledRole>,
+}
+
+impl InstalledApp {
+    fn new(info: AppInfo, core_app_id: &str) -> Self {
+        let (kind, hosted_happ_id) = parse_instance(&info.installed_app_id, core_app_id);
+        InstalledApp {
+            disabled_reason: get_disabled_reason(&info.status),
+            roles: get_roles(&info),
+            agent: info.agent_pub_key.into(),
+            installed_app_id: info.installed_app_id,
+            status: info.status,
+            installed_at: info.installed_at,
+            kind,
+            hosted_happ_id,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct InstalledRole {
+    pub role_name: String,
+    pub dna_hash: Option<DnaHashB64>, // hash of provisioned cell, or of the original DNA of a stem cell
+    pub agent: Option<AgentPubKeyB64>, // null for stem cells
+    pub clones: Vec<CloneCell>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct CloneCell {
+    pub clone_id: String,
+    pub name: String,
+    pub dna_hash: DnaHashB64,
+    pub agent: AgentPubKeyB64,
+    pub enabled: bool,
+}
+
+#[cfg(test)]
+mod test {
+    use holochain_types::dna::ActionHashB64;
+
+    use super::{parse_instance, InstanceKind};
+
+    #[test]
+    fn parses_instance_naming_conventions() {
+        let happ_id = "uhCkklkJVx4u17eCaaKg_phRJsHOj9u57v_4cHQR-Bd9tb-vePRyC";
+        let hosted_happ_id = Some(ActionHashB64::from_b64_str(happ_id).unwrap());
+
+        assert_eq!(
+            parse_instance("core-app", "core-app"),
+            (InstanceKind::Core, None)
+        );
+        assert_eq!(
+            parse_instance(happ_id, "core-app"),
+            (InstanceKind::Happ, hosted_happ_id.clone())
+        );
+        assert_eq!(
+            parse_instance(&format!("{}::servicelogger", happ_id), "core-app"),
+            (InstanceKind::Servicelogger, hosted_happ_id.clone())
+        );
+        assert_eq!(
+            parse_instance(
+                &format!(
+                    "{}:uhCAk76ikqpgxdisc5bRJcCY-lOTVB8osHEkiGj8hP4kxA01jSrjC",
+                    happ_id
+                ),
+                "core-app"
+            ),
+            (InstanceKind::Agent, hosted_happ_id)
+        );
+        assert_eq!(
+            parse_instance("my-notes", "core-app"),
+            (InstanceKind::Other, None)
+        );
+        assert_eq!(
+            parse_instance(&format!("{}:not-an-agent", happ_id), "core-app"),
+            (InstanceKind::Other, None)
+        );
+    }
+}
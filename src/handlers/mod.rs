@@ -1,3 +1,19 @@
+pub mod agents;
+pub mod auto_accept;
+pub mod bundles;
 pub mod hosted_happs;
+pub mod hosting_history;
 pub mod install;
+pub mod installed;
+pub mod preferences;
+pub mod preflight;
+pub mod published;
+pub mod quota;
+pub mod reconcile;
 pub mod register;
+pub mod self_hosted;
+pub mod storage;
+pub mod uninstall;
+pub mod upgrade;
+pub mod visibility;
+pub mod weblog;
@@ -0,0 +1,206 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use holochain_types::dna::ActionHashB64;
+use holofuel_types::fuel::Fuel;
+use hpos_hc_connect::{
+    app_connection::CoreAppRoleName, hha_agent::CoreAppAgent,
+    hha_types::HappPreferences as HostPreferences,
+};
+use log::debug;
+use rocket::serde::{Deserialize, Serialize};
+
+use super::install::{get_app_details, HappPreferences};
+use crate::common::types::PublisherPricingPref;
+use crate::hpos::Ws;
+use crate::routes::apps::hosted::ServiceloggerHappPreferences;
+
+/// Returns preferences in effect for happ `id` side by side with publisher's pricing and host's defaults
+pub async fn handle_get_happ_preferences(
+    ws: &mut Ws,
+    id: ActionHashB64,
+) -> Result<HappPreferencesResponse> {
+    let publisher = get_publisher_pricing_pref(ws, id.clone()).await?;
+
+    let app_connection = ws.get_connection(ws.core_app_id.clone()).await?;
+
+    debug!("calling zome hha/get_happ_preferences for happ {}", &id);
+    let effective: ServiceloggerHappPreferences = app_connection
+        .zome_call_typed(
+            CoreAppRoleName::HHA.into(),
+            "hha".into(),
+            "get_happ_preferences".into(),
+            id.clone(),
+        )
+        .await?;
+
+    let mut hha = CoreAppAgent::spawn(None).await?;
+    let host_default: HostPreferences = hha.get_host_preferences().await?;
+
+    Ok(HappPreferencesResponse {
+        happ_id: id,
+        price_compute: RateComparison {
+            effective: effective.price_compute,
+            publisher: publisher.cpu,
+            host_default: host_default.price_compute,
+        },
+        price_storage: RateComparison {
+            effective: effective.price_storage,
+            publisher: publisher.storage,
+            host_default: host_default.price_storage,
+        },
+        price_bandwidth: RateComparison {
+            effective: effective.price_bandwidth,
+            publisher: publisher.bandwidth,
+            host_default: host_default.price_bandwidth,
+        },
+        max_fuel_before_invoice: effective.max_fuel_before_invoice,
+        max_time_before_invoice: effective.max_time_before_invoice,
+    })
+}
+
+/// Saves host's preferences for happ `id` in hha, overriding host's defaults for this happ only.
+/// Preferences have to be checked with `validate_happ_preferences` first.
+pub async fn handle_set_happ_preferences(
+    ws: &mut Ws,
+    id: ActionHashB64,
+    preferences: HappPreferences,
+) -> Result<()> {
+    let payload = SetHappPreferencesInput {
+        happ_id: id,
+        preferences,
+    };
+
+    debug!(
+        "calling zome hha/set_happ_preferences with payload: {:?}",
+        &payload
+    );
+    let app_connection = ws.get_connection(ws.core_app_id.clone()).await?;
+
+    app_connection
+        .zome_call_typed::<SetHappPreferencesInput, ActionHashB64>(
+            CoreAppRoleName::HHA.into(),
+            "hha".into(),
+            "set_happ_preferences".into(),
+            payload,
+        )
+        .await?;
+
+    Ok(())
+}
+
+pub async fn get_publisher_pricing_pref(
+    ws: &mut Ws,
+    id: ActionHashB64,
+) -> Result<PublisherPricingPref> {
+    let app_connection = ws.get_connection(ws.core_app_id.clone()).await?;
+
+    Ok(get_app_details(app_connection, id.into())
+        .await?
+        .publisher_pricing_pref)
+}
+
+/// Host can not charge less than nothing, nor more than publisher is willing to pay
+pub fn validate_happ_preferences(
+    preferences: &HappPreferences,
+    publisher: &PublisherPricingPref,
+) -> Result<()> {
+    let zero = Fuel::new(0);
+
+    for (name, price, max_price) in [
+        ("price_compute", preferences.price_compute, publisher.cpu),
+        (
+            "price_storage",
+            preferences.price_storage,
+            publisher.storage,
+        ),
+        (
+            "price_bandwidth",
+            preferences.price_bandwidth,
+            publisher.bandwidth,
+        ),
+    ] {
+        if price < zero {
+            return Err(anyhow!("{} can not be negative", name));
+        }
+        if price > max_price {
+            return Err(anyhow!(
+                "{} of {} exceeds publisher's price of {}",
+                name,
+                price,
+                max_price
+            ));
+        }
+    }
+
+    if preferences.max_fuel_before_invoice < zero {
+        return Err(anyhow!("max_fuel_before_invoice can not be negative"));
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct SetHappPreferencesInput {
+    happ_id: ActionHashB64,
+    #[serde(flatten)]
+    preferences: HappPreferences,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct HappPreferencesResponse {
+    pub happ_id: ActionHashB64,
+    pub price_compute: RateComparison,
+    pub price_storage: RateComparison,
+    pub price_bandwidth: RateComparison,
+    pub max_fuel_before_invoice: Fuel,
+    pub max_time_before_invoice: Duration,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct RateComparison {
+    pub effective: Fuel,
+    pub publisher: Fuel,
+    pub host_default: Fuel,
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use holofuel_types::fuel::Fuel;
+
+    use super::validate_happ_preferences;
+    use crate::common::types::PublisherPricingPref;
+    use crate::handlers::install::HappPreferences;
+
+    fn preferences(price: &str) -> HappPreferences {
+        HappPreferences {
+            max_fuel_before_invoice: Fuel::from_str("1").unwrap(),
+            max_time_before_invoice: Duration::from_secs(60),
+            price_compute: Fuel::from_str(price).unwrap(),
+            price_storage: Fuel::from_str(price).unwrap(),
+            price_bandwidth: Fuel::from_str(price).unwrap(),
+        }
+    }
+
+    #[test]
+    fn validates_prices_against_publisher() {
+        let publisher = PublisherPricingPref {
+            cpu: Fuel::from_str("2").unwrap(),
+            storage: Fuel::from_str("2").unwrap(),
+            bandwidth: Fuel::from_str("2").unwrap(),
+        };
+
+        assert!(validate_happ_preferences(&preferences("0"), &publisher).is_ok());
+        assert!(validate_happ_preferences(&preferences("2"), &publisher).is_ok());
+        assert!(validate_happ_preferences(&preferences("3"), &publisher).is_err());
+        assert!(validate_happ_preferences(&preferences("-1"), &publisher).is_err());
+    }
+}
@@ -0,0 +1,443 @@
+/*
+Hosting Preflight Overview
+
+Before a host commits to hosting a happ, preflight tells whether the happ fits this holoport:
+- jurisdiction: host's jurisdiction registered in HBS is allowed by happ's `jurisdictions`,
+  which is a list of excluded jurisdictions when `exclude_jurisdictions` is set
+- pricing: publisher pays at least host's default prices from billing preferences
+- categories: happ's categories are allowed by `categories_prefs` of host's billing preferences,
+  which either lists categories host accepts or those it excludes
+- disk space: free space on the holoport is above HPOS_API_MIN_FREE_DISK bytes (1 GiB by default)
+- servicelogger: an instance of servicelogger can be installed for the happ
+
+Checks that could not be run (e.g. HBS is unreachable) are reported as unknown and do not fail preflight.
+*/
+
+use anyhow::{anyhow, Result};
+use holochain_client::AppInfo;
+use holochain_types::dna::ActionHashB64;
+use hpos_hc_connect::{
+    app_connection::CoreAppRoleName, hha_agent::CoreAppAgent,
+    hha_types::HappPreferences as HostPreferences,
+};
+use rocket::serde::{json::serde_json, Deserialize, Serialize};
+
+use super::install::{get_app_details, get_base_dna_hash, get_sl_id};
+use crate::common::hbs::HBS;
+use crate::common::types::{PresentedHappBundle, PublisherPricingPref};
+use crate::hpos::{get_available_disk_space, get_data_dir, Ws};
+
+const DEFAULT_MIN_FREE_DISK: u64 = 1024 * 1024 * 1024;
+
+/// Runs all preflight checks of hosting happ `id` on this holoport
+pub async fn handle_preflight(
+    ws: &mut Ws,
+    hbs: &mut HBS,
+    id: ActionHashB64,
+) -> Result<PreflightReport> {
+    let app_connection = ws.get_connection(ws.core_app_id.clone()).await?;
+    let happ: PresentedHappBundle = get_app_details(app_connection, id.clone().into()).await?;
+
+    let jurisdiction = match hbs.download_holo_client().await {
+        Ok(holo_client) => check_jurisdiction(
+            &happ.jurisdictions,
+            happ.exclude_jurisdictions,
+            &holo_client.jurisdiction,
+        ),
+        Err(e) => PreflightCheck::unknown(
+            "jurisdiction",
+            format!("Failed to read host's jurisdiction from HBS: {}", e),
+        ),
+    };
+
+    let (pricing, categories) = match get_host_preferences().await {
+        Ok(host) => (
+            check_pricing(&happ.publisher_pricing_pref, &host_prices(&host)),
+            check_categories(&happ.categories, get_categories_prefs(&host).as_ref()),
+        ),
+        Err(e) => {
+            let reason = format!("Failed to read host's billing preferences: {}", e);
+            (
+                PreflightCheck::unknown("pricing", reason.clone()),
+                PreflightCheck::unknown("categories", reason),
+            )
+        }
+    };
+
+    let data_dir = get_data_dir();
+    let disk_space = match get_available_disk_space(&data_dir) {
+        Ok(available) => check_disk_space(available, get_min_free_disk()),
+        Err(e) => PreflightCheck::unknown(
+            "diskSpace",
+            format!("Failed to read free disk space: {}", e),
+        ),
+    };
+
+    let servicelogger = check_servicelogger(ws, &id).await;
+
+    let checks = vec![jurisdiction, pricing, categories, disk_space, servicelogger];
+    Ok(PreflightReport {
+        happ_id: id,
+        passed: checks.iter().all(|c| c.status != CheckStatus::Fail),
+        checks,
+    })
+}
+
+async fn get_host_preferences() -> Result<HostPreferences> {
+    let mut hha = CoreAppAgent::spawn(None).await?;
+    hha.get_host_preferences().await
+}
+
+/// Host's default prices in the shape of publisher's pricing, so that they can be compared
+fn host_prices(host: &HostPreferences) -> PublisherPricingPref {
+    PublisherPricingPref {
+        cpu: host.price_compute,
+        storage: host.price_storage,
+        bandwidth: host.price_bandwidth,
+    }
+}
+
+/// Reads `categories_prefs` of host's preferences through their serialized form,
+/// `null` means that host has no preference
+fn get_categories_prefs(host: &HostPreferences) -> Option<ExclusivePreferences> {
+    serde_json::to_value(host)
+        .ok()
+        .and_then(|value| serde_json::from_value(value["categories_prefs"].clone()).ok())
+}
+
+fn get_min_free_disk() -> u64 {
+    std::env::var("HPOS_API_MIN_FREE_DISK")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_MIN_FREE_DISK)
+}
+
+pub(crate) fn check_jurisdiction(
+    jurisdictions: &[String],
+    exclude: bool,
+    host_jurisdiction: &str,
+) -> PreflightCheck {
+    let listed = jurisdictions
+        .iter()
+        .any(|j| j.eq_ignore_ascii_case(host_jurisdiction));
+
+    if exclude {
+        if listed {
+            return PreflightCheck::fail(
+                "jurisdiction",
+                format!("Happ excludes host's jurisdiction {}", host_jurisdiction),
+            );
+        }
+    } else if !jurisdictions.is_empty() && !listed {
+        return PreflightCheck::fail(
+            "jurisdiction",
+            format!(
+                "Happ is limited to jurisdictions {}, host is in {}",
+                jurisdictions.join(", "),
+                host_jurisdiction
+            ),
+        );
+    }
+    PreflightCheck::pass(
+        "jurisdiction",
+        format!("Happ can be hosted in {}", host_jurisdiction),
+    )
+}
+
+fn check_pricing(publisher: &PublisherPricingPref, host: &PublisherPricingPref) -> PreflightCheck {
+    let too_low: Vec<String> = [
+        ("cpu", publisher.cpu, host.cpu),
+        ("storage", publisher.storage, host.storage),
+        ("bandwidth", publisher.bandwidth, host.bandwidth),
+    ]
+    .iter()
+    .filter(|(_, offered, asked)| offered < asked)
+    .map(|(name, offered, asked)| format!("{} {} < {}", name, offered, asked))
+    .collect();
+
+    if too_low.is_empty() {
+        PreflightCheck::pass(
+            "pricing",
+            "Publisher pays at least host's prices".to_string(),
+        )
+    } else {
+        PreflightCheck::fail(
+            "pricing",
+            format!(
+                "Publisher pays less than host's prices: {}",
+                too_low.join(", ")
+            ),
+        )
+    }
+}
+
+fn check_categories(categories: &[String], prefs: Option<&ExclusivePreferences>) -> PreflightCheck {
+    let prefs = match prefs {
+        Some(prefs) if !prefs.value.is_empty() => prefs,
+        _ => {
+            return PreflightCheck::pass(
+                "categories",
+                "Host has no category preferences".to_string(),
+            )
+        }
+    };
+
+    let listed: Vec<&str> = categories
+        .iter()
+        .filter(|c| prefs.value.iter().any(|p| p.eq_ignore_ascii_case(c)))
+        .map(|c| c.as_str())
+        .collect();
+
+    if prefs.is_exclusion && !listed.is_empty() {
+        return PreflightCheck::fail(
+            "categories",
+            format!("Host excludes categories {}", listed.join(", ")),
+        );
+    }
+    if !prefs.is_exclusion && listed.is_empty() {
+        return PreflightCheck::fail(
+            "categories",
+            format!("Host accepts only categories {}", prefs.value.join(", ")),
+        );
+    }
+
+    PreflightCheck::pass(
+        "categories",
+        "Happ's categories are accepted by host".to_string(),
+    )
+}
+
+fn check_disk_space(available: u64, min_free: u64) -> PreflightCheck {
+    if available < min_free {
+        PreflightCheck::fail(
+            "diskSpace",
+            format!(
+                "{} bytes of disk space left, at least {} bytes are required",
+                available, min_free
+            ),
+        )
+    } else {
+        PreflightCheck::pass(
+            "diskSpace",
+            format!("{} bytes of disk space left", available),
+        )
+    }
+}
+
+/// Servicelogger instance is provisioned from the base servicelogger bundle
+/// with properties binding it to host's hha and holofuel DNAs
+async fn check_servicelogger(ws: &mut Ws, id: &ActionHashB64) -> PreflightCheck {
+    match servicelogger_status(ws, id).await {
+        Ok(check) => check,
+        Err(e) => PreflightCheck::unknown(
+            "servicelogger",
+            format!("Failed to check servicelogger: {}", e),
+        ),
+    }
+}
+
+async fn servicelogger_status(ws: &mut Ws, id: &ActionHashB64) -> Result<PreflightCheck> {
+    let sl_id = get_sl_id(&id.to_string());
+    let installed_apps: Vec<AppInfo> = ws
+        .admin
+        .list_apps(None)
+        .await
+        .map_err(|err| anyhow!("{:?}", err))?;
+    if installed_apps
+        .iter()
+        .any(|app| app.installed_app_id == sl_id)
+    {
+        return Ok(PreflightCheck::pass(
+            "servicelogger",
+            format!("Servicelogger {} is already installed", sl_id),
+        ));
+    }
+
+    let bundle_available = match (&ws.base_sl.bundle_url, &ws.base_sl.bundle_path) {
+        (Some(_), _) => true,
+        (None, Some(path)) => path.exists(),
+        (None, None) => false,
+    };
+    if !bundle_available {
+        return Ok(PreflightCheck::fail(
+            "servicelogger",
+            "Base servicelogger bundle is not available".to_string(),
+        ));
+    }
+
+    if std::env::var("SL_COLLECTOR_PUB_KEY").is_err() {
+        return Ok(PreflightCheck::fail(
+            "servicelogger",
+            "SL_COLLECTOR_PUB_KEY is not set".to_string(),
+        ));
+    }
+
+    let app_connection = ws.get_connection(ws.core_app_id.clone()).await?;
+    let cell_info = app_connection.app_info().await?.cell_info;
+    for role in [CoreAppRoleName::HHA, CoreAppRoleName::Holofuel] {
+        if let Err(e) = get_base_dna_hash(&cell_info, role.into()) {
+            return Ok(PreflightCheck::fail("servicelogger", e.to_string()));
+        }
+    }
+
+    Ok(PreflightCheck::pass(
+        "servicelogger",
+        "Servicelogger can be installed".to_string(),
+    ))
+}
+
+/// Host's preference of a list of values as stored in hha, `value` lists either
+/// the only accepted values, or excluded ones when `is_exclusion` is set
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct ExclusivePreferences {
+    pub value: Vec<String>,
+    pub is_exclusion: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct PreflightReport {
+    pub happ_id: ActionHashB64,
+    pub passed: bool, // true when no check has failed
+    pub checks: Vec<PreflightCheck>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct PreflightCheck {
+    pub name: String,
+    pub status: CheckStatus,
+    pub reason: String,
+}
+
+impl PreflightCheck {
+    fn pass(name: &str, reason: String) -> Self {
+        PreflightCheck {
+            name: name.to_string(),
+            status: CheckStatus::Pass,
+            reason,
+        }
+    }
+
+    fn fail(name: &str, reason: String) -> Self {
+        PreflightCheck {
+            name: name.to_string(),
+            status: CheckStatus::Fail,
+            reason,
+        }
+    }
+
+    fn unknown(name: &str, reason: String) -> Self {
+        PreflightCheck {
+            name: name.to_string(),
+            status: CheckStatus::Unknown,
+            reason,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    Unknown,
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use holofuel_types::fuel::Fuel;
+
+    use super::*;
+
+    fn fuel(amount: &str) -> Fuel {
+        Fuel::from_str(amount).unwrap()
+    }
+
+    fn categories(list: &[&str]) -> Vec<String> {
+        list.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn checks_host_jurisdiction() {
+        let jurisdictions = categories(&["Canada", "Germany"]);
+
+        assert_eq!(
+            check_jurisdiction(&jurisdictions, false, "germany").status,
+            CheckStatus::Pass
+        );
+        assert_eq!(
+            check_jurisdiction(&jurisdictions, false, "Poland").status,
+            CheckStatus::Fail
+        );
+        assert_eq!(
+            check_jurisdiction(&jurisdictions, true, "Germany").status,
+            CheckStatus::Fail
+        );
+        assert_eq!(
+            check_jurisdiction(&jurisdictions, true, "Poland").status,
+            CheckStatus::Pass
+        );
+        // no restrictions
+        assert_eq!(
+            check_jurisdiction(&[], false, "Poland").status,
+            CheckStatus::Pass
+        );
+    }
+
+    #[test]
+    fn checks_pricing_against_host_prices() {
+        let publisher = PublisherPricingPref {
+            cpu: fuel("2"),
+            storage: fuel("1"),
+            bandwidth: fuel("1"),
+        };
+        let mut host = PublisherPricingPref {
+            cpu: fuel("2"),
+            storage: fuel("1"),
+            bandwidth: fuel("0.5"),
+        };
+        assert_eq!(check_pricing(&publisher, &host).status, CheckStatus::Pass);
+
+        host.storage = fuel("1.5");
+        let check = check_pricing(&publisher, &host);
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(check.reason.contains("storage"));
+    }
+
+    #[test]
+    fn checks_categories_against_host_preferences() {
+        let accepted = ExclusivePreferences {
+            value: categories(&["Games", "Social"]),
+            is_exclusion: false,
+        };
+        let excluded = ExclusivePreferences {
+            value: categories(&["Gambling"]),
+            is_exclusion: true,
+        };
+        let check = |list: &[&str], prefs: &ExclusivePreferences| {
+            check_categories(&categories(list), Some(prefs)).status
+        };
+
+        assert_eq!(check(&["games"], &accepted), CheckStatus::Pass);
+        assert_eq!(check(&["Finance"], &accepted), CheckStatus::Fail);
+        assert_eq!(check(&["Games", "Gambling"], &excluded), CheckStatus::Fail);
+        assert_eq!(check(&["Finance"], &excluded), CheckStatus::Pass);
+        assert_eq!(
+            check_categories(&categories(&["Finance"]), None).status,
+            CheckStatus::Pass
+        );
+    }
+
+    #[test]
+    fn checks_disk_space() {
+        assert_eq!(check_disk_space(2048, 1024).status, CheckStatus::Pass);
+        assert_eq!(check_disk_space(512, 1024).status, CheckStatus::Fail);
+    }
+}
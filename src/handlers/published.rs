@@ -0,0 +1,239 @@
+// Publisher's view of happs registered in hha from this holoport. The same agent that hosts happs
+// also publishes them, so a happ is ours when its `provider_pubkey` is the host agent.
+// As a publisher the host is the payer of hosting invoices sent by hosts of these happs.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use holochain_types::{
+    dna::{ActionHashB64, AgentPubKeyB64},
+    prelude::Timestamp,
+};
+use holofuel_types::fuel::Fuel;
+use hpos_hc_connect::app_connection::CoreAppRoleName;
+use log::{debug, warn};
+use rocket::serde::{de::IgnoredAny, Deserialize, Serialize};
+
+use super::install::{get_app_details, get_host_pub_key};
+use crate::common::types::{
+    HappInput, PresentedHappBundle, TransactionDirection, TransactionStatus,
+};
+use crate::hpos::Ws;
+use crate::routes::host::shared::{
+    get_hosting_invoices, HostingInvoicesResponse, InvoiceSet, TransactionAndInvoiceDetails,
+};
+
+/// Lists happs published by host agent, drafts included, with number of their hosts and invoices paid for them
+pub async fn handle_get_published(ws: &mut Ws) -> Result<Vec<PublishedHapp>> {
+    let publisher = get_publisher(ws).await?;
+    let app_connection = ws.get_connection(ws.core_app_id.clone()).await?;
+
+    debug!("calling zome hha/get_my_happs");
+    let happs: Vec<PresentedHappBundle> = app_connection
+        .zome_call_typed(
+            CoreAppRoleName::HHA.into(),
+            "hha".into(),
+            "get_my_happs".into(),
+            (),
+        )
+        .await?;
+
+    let HostingInvoicesResponse {
+        transaction_and_invoice_details,
+        ..
+    } = get_hosting_invoices(app_connection.to_owned(), InvoiceSet::All).await?;
+    let mut received_invoices: Vec<TransactionAndInvoiceDetails> = transaction_and_invoice_details
+        .into_iter()
+        .filter(|invoice| invoice.direction == TransactionDirection::Incoming)
+        .collect();
+
+    let mut result = vec![];
+    for happ in happs
+        .into_iter()
+        .filter(|happ| happ.provider_pubkey == publisher)
+    {
+        let hosts_enabled = match count_hosts(ws, &happ.id).await {
+            Ok(count) => Some(count),
+            Err(e) => {
+                warn!("failed to read hosts of happ {}: {}", happ.id, e);
+                None
+            }
+        };
+
+        let (invoices, rest): (Vec<_>, Vec<_>) = received_invoices
+            .into_iter()
+            .partition(|invoice| invoice.happ.id == happ.id);
+        received_invoices = rest;
+
+        result.push(PublishedHapp {
+            id: happ.id,
+            name: happ.name,
+            bundle_url: happ.bundle_url,
+            is_draft: happ.is_draft,
+            is_paused: happ.is_paused,
+            last_edited: happ.last_edited,
+            hosts_enabled,
+            invoices: PublisherInvoices::new(invoices),
+        });
+    }
+
+    Ok(result)
+}
+
+/// Stores `payload` in hha as a draft, which is not offered to hosts until it is published
+pub async fn handle_create_draft(ws: &mut Ws, payload: HappInput) -> Result<PresentedHappBundle> {
+    let app_connection = ws.get_connection(ws.core_app_id.clone()).await?;
+
+    debug!("calling zome hha/create_draft with payload: {:?}", &payload);
+    let happ = app_connection
+        .zome_call_typed(
+            CoreAppRoleName::HHA.into(),
+            "hha".into(),
+            "create_draft".into(),
+            payload,
+        )
+        .await?;
+
+    Ok(happ)
+}
+
+/// Runs one of publisher's actions on happ `id` published from this holoport
+pub async fn handle_publisher_action(
+    ws: &mut Ws,
+    id: ActionHashB64,
+    action: PublisherAction,
+) -> Result<PresentedHappBundle> {
+    let publisher = get_publisher(ws).await?;
+    let app_connection = ws.get_connection(ws.core_app_id.clone()).await?;
+
+    let happ: PresentedHappBundle = get_app_details(app_connection, id.clone().into()).await?;
+    if happ.provider_pubkey != publisher {
+        return Err(anyhow!(
+            "Happ {} was published by {}, not by this holoport",
+            id,
+            happ.provider_pubkey
+        ));
+    }
+    match action {
+        PublisherAction::Publish if !happ.is_draft => {
+            return Err(anyhow!("Happ {} is already published", id))
+        }
+        PublisherAction::Pause if happ.is_paused => {
+            return Err(anyhow!("Happ {} is already paused", id))
+        }
+        PublisherAction::Unpause if !happ.is_paused => {
+            return Err(anyhow!("Happ {} is not paused", id))
+        }
+        _ => (),
+    }
+
+    debug!("calling zome hha/{} for happ {}", action.zome_fn(), &id);
+    let happ = app_connection
+        .zome_call_typed(
+            CoreAppRoleName::HHA.into(),
+            "hha".into(),
+            action.zome_fn().into(),
+            id,
+        )
+        .await?;
+
+    Ok(happ)
+}
+
+async fn get_publisher(ws: &mut Ws) -> Result<AgentPubKeyB64> {
+    let maybe_pubkey = ws.host_pub_key.clone();
+    let app_connection = ws.get_connection(ws.core_app_id.clone()).await?;
+    Ok(get_host_pub_key(maybe_pubkey, app_connection).await?.into())
+}
+
+/// Number of holoports that have enabled happ `id`
+async fn count_hosts(ws: &mut Ws, id: &ActionHashB64) -> Result<usize> {
+    let app_connection = ws.get_connection(ws.core_app_id.clone()).await?;
+
+    debug!("calling zome hha/get_hosts for happ {}", id);
+    // only the number of hosts is of interest, not their details
+    let hosts: Vec<IgnoredAny> = app_connection
+        .zome_call_typed(
+            CoreAppRoleName::HHA.into(),
+            "hha".into(),
+            "get_hosts".into(),
+            id.clone(),
+        )
+        .await?;
+
+    Ok(hosts.len())
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PublisherAction {
+    Publish,
+    Pause,
+    Unpause,
+}
+
+impl PublisherAction {
+    fn zome_fn(&self) -> &'static str {
+        match self {
+            PublisherAction::Publish => "publish_happ",
+            PublisherAction::Pause => "pause_happ",
+            PublisherAction::Unpause => "unpause_happ",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct PublishedHapp {
+    pub id: ActionHashB64,
+    pub name: String,
+    pub bundle_url: String,
+    pub is_draft: bool,
+    pub is_paused: bool,
+    pub last_edited: Timestamp,
+    pub hosts_enabled: Option<usize>, // null when hosts could not be read from hha
+    pub invoices: PublisherInvoices,
+}
+
+/// Hosting invoices received for a happ, in which the publisher is the payer
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct PublisherInvoices {
+    pub paid: Fuel,
+    pub unpaid: Fuel,
+    pub invoices: Vec<TransactionAndInvoiceDetails>,
+}
+
+impl PublisherInvoices {
+    fn new(invoices: Vec<TransactionAndInvoiceDetails>) -> Self {
+        let mut paid = Fuel::new(0);
+        let mut unpaid = Fuel::new(0);
+        for invoice in invoices.iter() {
+            let amount = match Fuel::from_str(&invoice.amount) {
+                Ok(amount) => amount,
+                Err(e) => {
+                    warn!(
+                        "failed to parse amount {} of invoice {}: {:?}",
+                        invoice.amount, invoice.id, e
+                    );
+                    continue;
+                }
+            };
+            let total = match invoice.status {
+                TransactionStatus::Completed => &mut paid,
+                _ => &mut unpaid,
+            };
+            match *total + amount {
+                Ok(sum) => *total = sum,
+                Err(e) => warn!("failed to sum invoice amounts: {:?}", e),
+            }
+        }
+
+        PublisherInvoices {
+            paid,
+            unpaid,
+            invoices,
+        }
+    }
+}
@@ -0,0 +1,469 @@
+/*
+Disk Quotas Overview
+
+Host can limit disk space used by hosted happs with quotas kept in `<HPOS_API_DATA_DIR>/disk_quotas.json`:
+- per-happ quota, either set for a happ or the default one applied to all other happs
+- global quota on disk space used by all enabled happs together
+- minimum free disk space on the file system holding the API's data
+
+Disk usage of a happ is the total size of files in the latest servicelogger `DiskUsageLog`.
+Quotas are checked every HPOS_API_QUOTA_INTERVAL seconds (1 hour by default, 0 turns checking off).
+A happ over its own quota is auto-disabled. When global quota or free disk space are exceeded,
+the largest happs are auto-disabled until enough space is accounted for.
+Auto-disabling does not free any disk, data of a disabled happ stays on the holoport. So disk used by happs
+that are already auto-disabled counts as accounted for, otherwise every check would disable another happ
+for as long as the disk stays full.
+
+Auto-disabling disables happ in hha and stops its instances in holochain, so that its databases stop growing.
+hha has no zome call that sets `HostSettings.is_auto_disabled`, `disable_happ` takes only `HappAndHost`
+and always records a disable by host. Instead the reason, the happ's disk usage and the fact that
+it was auto-disabled are recorded in `<HPOS_API_DATA_DIR>/auto_disabled.json` and reported in `HappDetails`.
+The record is cleared when host enables the happ again.
+*/
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use holochain_types::{
+    dna::ActionHashB64,
+    prelude::{ActionType, ChainQueryFilter, Timestamp},
+};
+use log::{debug, info, warn};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio::{self, time};
+
+use super::hosted_happs::{
+    get_all_happs, handle_disable, query_service_logs, to_log_entry, LogEntry,
+};
+use super::install::handle_holochain_disable;
+use crate::common::store::{load_json, save_json};
+use crate::common::types::HappAndHost;
+use crate::hpos::{get_available_disk_space, get_data_dir, Ws, WsMutex};
+
+const DEFAULT_INTERVAL: u64 = 3600;
+
+/// Checks quotas every HPOS_API_QUOTA_INTERVAL seconds in the background, as long as any quota is set
+pub fn spawn_quota_checker(wsm: WsMutex) {
+    let interval = Duration::from_secs(
+        std::env::var("HPOS_API_QUOTA_INTERVAL")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_INTERVAL),
+    );
+    if interval.is_zero() {
+        info!("checking of disk quotas is turned off");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut ticker = time::interval_at(time::Instant::now() + interval, interval);
+        loop {
+            ticker.tick().await;
+            match load_quotas() {
+                Ok(quotas) if quotas.is_empty() => continue,
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("failed to read disk quotas: {}", e);
+                    continue;
+                }
+            }
+            if let Err(e) = handle_check_quotas(&mut *wsm.lock().await).await {
+                warn!("checking of disk quotas failed: {}", e);
+            }
+        }
+    });
+}
+
+pub fn handle_get_quotas() -> Result<DiskQuotas> {
+    load_quotas()
+}
+
+pub fn handle_set_quotas(quotas: DiskQuotas) -> Result<DiskQuotas> {
+    for happ_id in quotas.happ_quotas.keys() {
+        ActionHashB64::from_b64_str(happ_id)
+            .map_err(|e| anyhow!("Invalid happ id {}: {}", happ_id, e))?;
+    }
+    save_json(&get_data_dir().join("disk_quotas.json"), &quotas)?;
+    Ok(quotas)
+}
+
+/// Checks disk usage of enabled happs against quotas and auto-disables happs over quota
+pub async fn handle_check_quotas(ws: &mut Ws) -> Result<QuotaReport> {
+    let quotas = load_quotas()?;
+
+    let mut usages = vec![];
+    for happ in get_all_happs(ws).await? {
+        // happs with special_installed_app_id run as core apps, which can not be disabled
+        if !happ.host_settings.is_enabled || happ.special_installed_app_id.is_some() {
+            continue;
+        }
+        let usage = match get_disk_usage(ws, &happ.id).await {
+            Ok(usage) => usage,
+            Err(e) => {
+                warn!("failed to read disk usage of happ {}: {}", happ.id, e);
+                None
+            }
+        };
+        usages.push(HappUsage {
+            quota: quotas.happ_quota(&happ.id.to_string()),
+            happ_id: happ.id.to_string(),
+            usage,
+        });
+    }
+
+    let free_disk = match get_available_disk_space(&get_data_dir()) {
+        Ok(free) => Some(free),
+        Err(e) => {
+            warn!("failed to read free disk space: {}", e);
+            None
+        }
+    };
+
+    let accounted: u64 = load_auto_disabled()?
+        .values()
+        .filter_map(|record| record.usage)
+        .sum();
+
+    let mut disabled = vec![];
+    for (happ_id, reason) in find_over_quota(&usages, &quotas, free_disk, accounted) {
+        let usage = usages
+            .iter()
+            .find(|happ| happ.happ_id == happ_id)
+            .and_then(|happ| happ.usage);
+        match auto_disable(ws, &happ_id, &reason, usage).await {
+            Ok(record) => disabled.push(record),
+            Err(e) => warn!("failed to auto-disable happ {}: {}", happ_id, e),
+        }
+    }
+
+    Ok(QuotaReport {
+        checked_at: Timestamp::now(),
+        free_disk,
+        happs: usages,
+        disabled,
+    })
+}
+
+/// Reason happ `id` has been auto-disabled for, if it has been
+pub fn get_auto_disabled_reason(id: &ActionHashB64) -> Option<String> {
+    match load_auto_disabled() {
+        Ok(mut records) => records.remove(&id.to_string()).map(|record| record.reason),
+        Err(e) => {
+            warn!("failed to read auto-disabled happs: {}", e);
+            None
+        }
+    }
+}
+
+/// Forgets that happ `id` has been auto-disabled, returns whether it has been
+pub fn clear_auto_disabled(id: &ActionHashB64) -> Result<bool> {
+    let mut records = load_auto_disabled()?;
+    if records.remove(&id.to_string()).is_none() {
+        return Ok(false);
+    }
+    save_json(&get_data_dir().join("auto_disabled.json"), &records)?;
+    Ok(true)
+}
+
+/// Returns total size of files in the latest disk usage log of happ `id`
+async fn get_disk_usage(ws: &mut Ws, id: &ActionHashB64) -> Result<Option<u64>> {
+    let filter = ChainQueryFilter::new()
+        .include_entries(true)
+        .action_type(ActionType::Create);
+    let records = query_service_logs(ws, id, filter).await?;
+
+    Ok(records
+        .iter()
+        .rev()
+        .find_map(|record| match to_log_entry(record) {
+            Some(LogEntry::DiskUsageLog(log)) => Some(log.files.iter().map(|file| file.size).sum()),
+            _ => None,
+        }))
+}
+
+/// Selects happs to be auto-disabled, each with the reason for it.
+/// `accounted` is disk used by happs auto-disabled earlier, it already covers part of free disk shortfall.
+fn find_over_quota(
+    usages: &[HappUsage],
+    quotas: &DiskQuotas,
+    free_disk: Option<u64>,
+    accounted: u64,
+) -> Vec<(String, String)> {
+    let mut over_quota: Vec<(String, String)> = vec![];
+
+    for happ in usages {
+        if let (Some(usage), Some(quota)) = (happ.usage, happ.quota) {
+            if usage > quota {
+                over_quota.push((
+                    happ.happ_id.clone(),
+                    format!(
+                        "Happ uses {} bytes of disk, its quota is {} bytes",
+                        usage, quota
+                    ),
+                ));
+            }
+        }
+    }
+
+    // largest happs are disabled first
+    let mut remaining: Vec<(&str, u64)> = usages
+        .iter()
+        .filter(|happ| !over_quota.iter().any(|(id, _)| id == &happ.happ_id))
+        .filter_map(|happ| happ.usage.map(|usage| (happ.happ_id.as_str(), usage)))
+        .collect();
+    remaining.sort_by(|a, b| b.1.cmp(&a.1));
+    let mut total: u64 = remaining.iter().map(|(_, usage)| usage).sum();
+    let mut remaining = remaining.into_iter();
+
+    if let Some(global_quota) = quotas.global_quota {
+        let reason = format!(
+            "Hosted happs use {} bytes of disk together, global quota is {} bytes",
+            total, global_quota
+        );
+        while total > global_quota {
+            match remaining.next() {
+                Some((id, usage)) => {
+                    over_quota.push((id.to_string(), reason.clone()));
+                    total -= usage;
+                }
+                None => break,
+            }
+        }
+    }
+
+    if let (Some(min_free), Some(free)) = (quotas.min_free_disk, free_disk) {
+        let reason = format!(
+            "{} bytes of disk are free, at least {} bytes are required",
+            free, min_free
+        );
+        let mut shortfall = min_free.saturating_sub(free).saturating_sub(accounted);
+        while shortfall > 0 {
+            match remaining.next() {
+                Some((id, usage)) => {
+                    over_quota.push((id.to_string(), reason.clone()));
+                    shortfall = shortfall.saturating_sub(usage.max(1));
+                }
+                None => break,
+            }
+        }
+    }
+
+    over_quota
+}
+
+/// Disables happ in hha and stops all of its instances except servicelogger
+async fn auto_disable(
+    ws: &mut Ws,
+    happ_id: &str,
+    reason: &str,
+    usage: Option<u64>,
+) -> Result<AutoDisabled> {
+    info!("auto-disabling happ {}: {}", happ_id, reason);
+
+    handle_disable(ws, HappAndHost::init(happ_id).await?).await?;
+
+    let instances: Vec<String> = ws
+        .admin
+        .list_apps(None)
+        .await
+        .map_err(|err| anyhow!("{:?}", err))?
+        .into_iter()
+        .map(|info| info.installed_app_id)
+        .filter(|id| {
+            id == happ_id
+                || (id.starts_with(&format!("{}:", happ_id)) && !id.ends_with("::servicelogger"))
+        })
+        .collect();
+    for installed_app_id in instances {
+        debug!("disabling instance {}", installed_app_id);
+        if let Err(e) = handle_holochain_disable(&mut ws.admin, &installed_app_id).await {
+            warn!("failed to disable instance {}: {}", installed_app_id, e);
+        }
+        ws.apps.remove(&installed_app_id);
+    }
+
+    let record = AutoDisabled {
+        happ_id: happ_id.to_string(),
+        reason: reason.to_string(),
+        disabled_at: Timestamp::now(),
+        usage,
+    };
+    let mut records = load_auto_disabled()?;
+    records.insert(happ_id.to_string(), record.clone());
+    save_json(&get_data_dir().join("auto_disabled.json"), &records)?;
+
+    Ok(record)
+}
+
+fn load_quotas() -> Result<DiskQuotas> {
+    load_json(&get_data_dir().join("disk_quotas.json"))
+}
+
+fn load_auto_disabled() -> Result<HashMap<String, AutoDisabled>> {
+    load_json(&get_data_dir().join("auto_disabled.json"))
+}
+
+/// Quotas in bytes, a quota that is not set is not checked
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct DiskQuotas {
+    pub default_happ_quota: Option<u64>,
+    #[serde(default)]
+    pub happ_quotas: HashMap<String, u64>, // happ id -> quota, overrides default_happ_quota
+    pub global_quota: Option<u64>,
+    pub min_free_disk: Option<u64>,
+}
+
+impl DiskQuotas {
+    fn is_empty(&self) -> bool {
+        self.default_happ_quota.is_none()
+            && self.happ_quotas.is_empty()
+            && self.global_quota.is_none()
+            && self.min_free_disk.is_none()
+    }
+
+    fn happ_quota(&self, happ_id: &str) -> Option<u64> {
+        self.happ_quotas
+            .get(happ_id)
+            .copied()
+            .or(self.default_happ_quota)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaReport {
+    pub checked_at: Timestamp,
+    pub free_disk: Option<u64>, // null when free disk space could not be read
+    pub happs: Vec<HappUsage>,
+    pub disabled: Vec<AutoDisabled>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct HappUsage {
+    pub happ_id: String,
+    pub usage: Option<u64>, // null when happ has no disk usage log yet
+    pub quota: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct AutoDisabled {
+    pub happ_id: String,
+    pub reason: String,
+    pub disabled_at: Timestamp,
+    #[serde(default)]
+    pub usage: Option<u64>, // disk used by happ when it was disabled
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::{find_over_quota, DiskQuotas, HappUsage};
+
+    fn usage(happ_id: &str, usage: u64, quota: Option<u64>) -> HappUsage {
+        HappUsage {
+            happ_id: happ_id.to_string(),
+            usage: Some(usage),
+            quota,
+        }
+    }
+
+    fn ids(over_quota: Vec<(String, String)>) -> Vec<String> {
+        over_quota.into_iter().map(|(id, _)| id).collect()
+    }
+
+    #[test]
+    fn disables_happs_over_own_quota() {
+        let usages = vec![usage("a", 500, Some(100)), usage("b", 50, Some(100))];
+        let quotas = DiskQuotas {
+            default_happ_quota: Some(100),
+            ..Default::default()
+        };
+        let over_quota = find_over_quota(&usages, &quotas, None, 0);
+        assert_eq!(over_quota.len(), 1);
+        assert_eq!(over_quota[0].0, "a");
+        assert_eq!(
+            over_quota[0].1,
+            "Happ uses 500 bytes of disk, its quota is 100 bytes"
+        );
+    }
+
+    #[test]
+    fn disables_largest_happs_over_global_quota() {
+        let usages = vec![
+            usage("a", 300, None),
+            usage("b", 500, None),
+            usage("c", 100, None),
+            usage("d", 1000, Some(900)),
+        ];
+        let quotas = DiskQuotas {
+            happ_quotas: HashMap::from([("d".to_string(), 900)]),
+            global_quota: Some(500),
+            ..Default::default()
+        };
+        // d is over its own quota, b brings the rest under global quota
+        assert_eq!(
+            ids(find_over_quota(&usages, &quotas, None, 0)),
+            vec!["d", "b"]
+        );
+    }
+
+    #[test]
+    fn disables_largest_happs_when_disk_is_full() {
+        let usages = vec![
+            usage("a", 300, None),
+            usage("b", 500, None),
+            usage("c", 100, None),
+        ];
+        let quotas = DiskQuotas {
+            min_free_disk: Some(1000),
+            ..Default::default()
+        };
+        assert!(find_over_quota(&usages, &quotas, Some(2000), 0).is_empty());
+        assert_eq!(
+            ids(find_over_quota(&usages, &quotas, Some(400), 0)),
+            vec!["b", "a"]
+        );
+        // free disk space is not checked when it could not be read
+        assert!(find_over_quota(&usages, &quotas, None, 0).is_empty());
+    }
+
+    #[test]
+    fn disables_once_per_full_disk() {
+        let usages = vec![
+            usage("a", 300, None),
+            usage("b", 500, None),
+            usage("c", 100, None),
+            usage("d", 50, None),
+        ];
+        let quotas = DiskQuotas {
+            min_free_disk: Some(1000),
+            ..Default::default()
+        };
+
+        // first check disables enough to cover the shortfall
+        let disabled = ids(find_over_quota(&usages, &quotas, Some(400), 0));
+        assert_eq!(disabled, vec!["b", "a"]);
+
+        // disabling has freed no disk, next check only sees the remaining happs
+        let remaining: Vec<HappUsage> = usages
+            .into_iter()
+            .filter(|happ| !disabled.contains(&happ.happ_id))
+            .collect();
+        assert!(find_over_quota(&remaining, &quotas, Some(400), 800).is_empty());
+
+        // disk got fuller meanwhile, only the difference is disabled
+        assert_eq!(
+            ids(find_over_quota(&remaining, &quotas, Some(100), 800)),
+            vec!["c"]
+        );
+    }
+}
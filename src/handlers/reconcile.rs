@@ -0,0 +1,415 @@
+/*
+Hosting Reconciliation Overview
+
+Whether a happ is hosted is recorded twice: in hha (`host_settings.is_enabled`) and in holochain, as the happ's
+instance (`<happ_id>`) and its servicelogger instance (`<happ_id>::servicelogger`). The two can drift apart,
+e.g. when an installation failed half-way or an app was disabled directly in the conductor.
+
+Reconciler periodically compares `hha/get_happs` with `admin.list_apps` and reports drift found:
+- happ is enabled in hha, but its instance is not installed -> repaired by installing the happ, unless it has been
+  installed with membrane proofs before, which the reconciler does not have -> reported as needing manual action
+- happ is enabled in hha, but its servicelogger is not installed -> repaired by installing the servicelogger only,
+  the happ's instance is never touched by it
+- happ is enabled in hha, but its instance or servicelogger is not running -> repaired by enabling the app
+- happ is disabled in hha, but its instance is running -> repaired by disabling the instance (servicelogger is kept)
+
+Happs with `special_installed_app_id` run as a core app, so only their servicelogger is checked.
+Repair is run only when HPOS_API_RECONCILE_REPAIR=true, or on request. Every repair action is recorded
+in `<HPOS_API_DATA_DIR>/reconcile_log.json`.
+
+Reconciliation runs every HPOS_API_RECONCILE_INTERVAL seconds (10 minutes by default), 0 turns it off.
+*/
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use holochain_conductor_api::AppInfoStatus;
+use holochain_types::prelude::Timestamp;
+use log::{debug, info, warn};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio::{self, sync::Mutex, time};
+
+use super::bundles;
+use super::hosted_happs::get_all_happs;
+use super::install::{
+    self, get_sl_id, handle_holochain_disable, handle_holochain_enable, JobProgress,
+};
+use crate::common::store::{load_json, save_json};
+use crate::common::types::PresentedHappBundle;
+use crate::hpos::{get_data_dir, WsMutex};
+
+const DEFAULT_INTERVAL: u64 = 600;
+
+/// Max number of repair actions kept in the log, the oldest ones are dropped first
+const MAX_LOG_ENTRIES: usize = 1000;
+
+pub type ReconcilerMutex = Arc<Mutex<Reconciler>>;
+
+/// Keeps the report of the last reconciliation
+#[derive(Default)]
+pub struct Reconciler {
+    last_report: Option<DriftReport>,
+}
+
+impl Reconciler {
+    pub fn new() -> ReconcilerMutex {
+        Arc::new(Mutex::new(Reconciler::default()))
+    }
+
+    pub fn last_report(&self) -> Option<DriftReport> {
+        self.last_report.clone()
+    }
+}
+
+/// Runs reconciliation every HPOS_API_RECONCILE_INTERVAL seconds in the background.
+/// First run happens one interval after start, so that it does not compete with start up of the API.
+pub fn spawn_reconciler(wsm: WsMutex, reconciler: ReconcilerMutex) {
+    let interval = get_interval();
+    if interval.is_zero() {
+        info!("reconciliation of hosted happs is turned off");
+        return;
+    }
+    let repair = get_auto_repair();
+
+    tokio::spawn(async move {
+        let mut ticker = time::interval_at(time::Instant::now() + interval, interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = handle_reconcile(&wsm, &reconciler, repair).await {
+                warn!("reconciliation of hosted happs failed: {}", e);
+            }
+        }
+    });
+}
+
+/// Compares hosting state in hha with apps in holochain and repairs drift found if `repair` is set
+pub async fn handle_reconcile(
+    wsm: &WsMutex,
+    reconciler: &ReconcilerMutex,
+    repair: bool,
+) -> Result<DriftReport> {
+    let mut report = check_drift(wsm).await?;
+    debug!("found {} drifts of hosted happs", report.drifts.len());
+
+    if repair {
+        for drift in report.drifts.iter_mut() {
+            drift.repair = Some(repair_drift(wsm, drift).await);
+        }
+    }
+
+    reconciler.lock().await.last_report = Some(report.clone());
+    Ok(report)
+}
+
+/// Returns repair actions taken so far, newest first
+pub fn handle_get_reconcile_log() -> Result<Vec<RepairAction>> {
+    let mut log = load_log()?;
+    log.actions.reverse();
+    Ok(log.actions)
+}
+
+async fn check_drift(wsm: &WsMutex) -> Result<DriftReport> {
+    let mut ws = wsm.lock().await;
+
+    let happs = get_all_happs(&mut ws).await?;
+    let apps: HashMap<String, bool> = ws
+        .admin
+        .list_apps(None)
+        .await
+        .map_err(|err| anyhow!("{:?}", err))?
+        .into_iter()
+        .map(|info| {
+            let running = matches!(info.status, AppInfoStatus::Running);
+            (info.installed_app_id, running)
+        })
+        .collect();
+
+    let states: Vec<HostingState> = happs.iter().map(HostingState::from).collect();
+
+    Ok(DriftReport {
+        checked_at: Timestamp::now(),
+        drifts: find_drift(&states, &apps),
+    })
+}
+
+async fn repair_drift(wsm: &WsMutex, drift: &Drift) -> RepairAction {
+    let action = match drift.kind.repair() {
+        RepairKind::Install if needs_membrane_proofs(&drift.happ_id) => RepairKind::Manual,
+        action => action,
+    };
+    let installed_app_id = match drift.kind {
+        DriftKind::ServiceloggerNotRunning => get_sl_id(&drift.happ_id),
+        _ => drift.happ_id.clone(),
+    };
+
+    let result = match action {
+        RepairKind::Install => {
+            let payload = install::InstallHappBody {
+                happ_id: drift.happ_id.clone(),
+                membrane_proofs: HashMap::new(),
+            };
+            install::handle_install_app(wsm, payload, &JobProgress::default())
+                .await
+                .map(|_| ())
+        }
+        RepairKind::InstallServicelogger => {
+            install::handle_install_servicelogger(wsm, &drift.happ_id)
+                .await
+                .map(|_| ())
+        }
+        RepairKind::Manual => Err(anyhow!(
+            "Happ {} has been installed with membrane proofs, it has to be installed again with them through POST /apps/hosted/install",
+            drift.happ_id
+        )),
+        RepairKind::Enable => {
+            handle_holochain_enable(&mut wsm.lock().await.admin, &installed_app_id)
+                .await
+                .map(|_| ())
+        }
+        RepairKind::Disable => {
+            let mut ws = wsm.lock().await;
+            let result = handle_holochain_disable(&mut ws.admin, &installed_app_id).await;
+            ws.apps.remove(&installed_app_id);
+            result
+        }
+    };
+
+    let repair = RepairAction {
+        happ_id: drift.happ_id.clone(),
+        drift: drift.kind,
+        action,
+        success: result.is_ok(),
+        error: result.err().map(|e| e.to_string()),
+        at: Timestamp::now(),
+    };
+    match &repair.error {
+        None => info!(
+            "reconciliation: {:?} of happ {} repaired by {:?}",
+            repair.drift, repair.happ_id, action
+        ),
+        Some(e) => warn!(
+            "reconciliation: failed to repair {:?} of happ {} by {:?}: {}",
+            repair.drift, repair.happ_id, action, e
+        ),
+    }
+    if let Err(e) = append_log(repair.clone()) {
+        warn!("failed to record repair action: {}", e);
+    }
+    repair
+}
+
+/// Happs installed with membrane proofs can not be reinstalled without them. Proofs are known only for happs
+/// whose installation has been recorded, others are attempted without proofs.
+fn needs_membrane_proofs(happ_id: &str) -> bool {
+    match bundles::get_install(happ_id) {
+        Ok(install) => install.is_some_and(|install| !install.membrane_proofs.is_empty()),
+        Err(e) => {
+            warn!("failed to read install record of happ {}: {}", happ_id, e);
+            false
+        }
+    }
+}
+
+/// Compares hosting state of happs in hha with installed apps, given as installed_app_id -> is running
+fn find_drift(happs: &[HostingState], apps: &HashMap<String, bool>) -> Vec<Drift> {
+    let mut drifts = vec![];
+    for happ in happs {
+        let sl_id = get_sl_id(&happ.happ_id);
+        let instance = if happ.is_special {
+            None
+        } else {
+            Some(apps.get(&happ.happ_id))
+        };
+
+        let kind = if happ.is_enabled {
+            match (instance, apps.get(&sl_id)) {
+                (Some(None), _) => Some(DriftKind::NotInstalled),
+                (_, None) => Some(DriftKind::ServiceloggerNotInstalled),
+                (Some(Some(false)), _) => Some(DriftKind::NotRunning),
+                (_, Some(false)) => Some(DriftKind::ServiceloggerNotRunning),
+                _ => None,
+            }
+        } else {
+            match instance {
+                Some(Some(true)) => Some(DriftKind::RunningButDisabled),
+                _ => None,
+            }
+        };
+
+        if let Some(kind) = kind {
+            drifts.push(Drift {
+                happ_id: happ.happ_id.clone(),
+                kind,
+                repair: None,
+            });
+        }
+    }
+    drifts
+}
+
+fn get_interval() -> Duration {
+    Duration::from_secs(
+        std::env::var("HPOS_API_RECONCILE_INTERVAL")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_INTERVAL),
+    )
+}
+
+fn get_auto_repair() -> bool {
+    std::env::var("HPOS_API_RECONCILE_REPAIR").is_ok_and(|s| s == "true")
+}
+
+fn append_log(action: RepairAction) -> Result<()> {
+    let mut log = load_log()?;
+    log.actions.push(action);
+    let excess = log.actions.len().saturating_sub(MAX_LOG_ENTRIES);
+    log.actions.drain(..excess);
+    save_json(&get_data_dir().join("reconcile_log.json"), &log)
+}
+
+fn load_log() -> Result<ReconcileLog> {
+    load_json(&get_data_dir().join("reconcile_log.json"))
+}
+
+/// Part of happ's record in hha that decides how it should be installed
+struct HostingState {
+    happ_id: String,
+    is_enabled: bool,
+    is_special: bool,
+}
+
+impl From<&PresentedHappBundle> for HostingState {
+    fn from(happ: &PresentedHappBundle) -> Self {
+        HostingState {
+            happ_id: happ.id.to_string(),
+            is_enabled: happ.host_settings.is_enabled,
+            is_special: happ.special_installed_app_id.is_some(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(crate = "rocket::serde")]
+struct ReconcileLog {
+    actions: Vec<RepairAction>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct DriftReport {
+    pub checked_at: Timestamp,
+    pub drifts: Vec<Drift>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct Drift {
+    pub happ_id: String,
+    pub kind: DriftKind,
+    pub repair: Option<RepairAction>, // null when repair was not run
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub enum DriftKind {
+    NotInstalled,              // enabled in hha, happ's instance is not installed
+    NotRunning,                // enabled in hha, happ's instance is not running
+    ServiceloggerNotInstalled, // enabled in hha, servicelogger instance is not installed
+    ServiceloggerNotRunning,   // enabled in hha, servicelogger instance is not running
+    RunningButDisabled,        // disabled in hha, happ's instance is running
+}
+
+impl DriftKind {
+    fn repair(&self) -> RepairKind {
+        match self {
+            DriftKind::NotInstalled => RepairKind::Install,
+            DriftKind::ServiceloggerNotInstalled => RepairKind::InstallServicelogger,
+            DriftKind::NotRunning | DriftKind::ServiceloggerNotRunning => RepairKind::Enable,
+            DriftKind::RunningButDisabled => RepairKind::Disable,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub enum RepairKind {
+    Install,
+    InstallServicelogger,
+    Enable,
+    Disable,
+    Manual, // drift can not be repaired automatically, `error` says what to do
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct RepairAction {
+    pub happ_id: String,
+    pub drift: DriftKind,
+    pub action: RepairKind,
+    pub success: bool,
+    pub error: Option<String>,
+    pub at: Timestamp,
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::{find_drift, DriftKind, HostingState};
+
+    fn happ(id: &str, is_enabled: bool, is_special: bool) -> HostingState {
+        HostingState {
+            happ_id: id.to_string(),
+            is_enabled,
+            is_special,
+        }
+    }
+
+    #[test]
+    fn finds_drift_between_hha_and_holochain() {
+        let happs = vec![
+            happ("ok", true, false),
+            happ("missing", true, false),
+            happ("stopped", true, false),
+            happ("no_sl", true, false),
+            happ("disabled", false, false),
+            happ("disabled_ok", false, false),
+            happ("special", true, true),
+        ];
+        let apps = HashMap::from([
+            ("ok".to_string(), true),
+            ("ok::servicelogger".to_string(), true),
+            ("missing::servicelogger".to_string(), true),
+            ("stopped".to_string(), false),
+            ("stopped::servicelogger".to_string(), true),
+            ("no_sl".to_string(), true),
+            ("disabled".to_string(), true),
+            ("disabled::servicelogger".to_string(), true),
+            ("disabled_ok".to_string(), false),
+            ("special::servicelogger".to_string(), false),
+        ]);
+
+        let drifts: Vec<(String, DriftKind)> = find_drift(&happs, &apps)
+            .into_iter()
+            .map(|drift| (drift.happ_id, drift.kind))
+            .collect();
+        assert_eq!(
+            drifts,
+            vec![
+                ("missing".to_string(), DriftKind::NotInstalled),
+                ("stopped".to_string(), DriftKind::NotRunning),
+                ("no_sl".to_string(), DriftKind::ServiceloggerNotInstalled),
+                ("disabled".to_string(), DriftKind::RunningButDisabled),
+                ("special".to_string(), DriftKind::ServiceloggerNotRunning),
+            ]
+        );
+    }
+}
@@ -1,9 +1,14 @@
 use crate::common::types::{HappInput, PresentedHappBundle};
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use holochain_types::dna::{ActionHashB64, AgentPubKeyB64};
 use hpos_hc_connect::app_connection::CoreAppRoleName;
 
 pub mod types;
+mod validation;
+use super::install::{get_app_details, get_host_pub_key};
 use crate::hpos::Ws;
+use types::UpdateHappInput;
+pub use validation::validate_happ_input;
 
 pub async fn handle_register_app(ws: &mut Ws, payload: HappInput) -> Result<PresentedHappBundle> {
     log::debug!("calling zome hosted/register with payload: {:?}", &payload);
@@ -20,3 +25,39 @@ pub async fn handle_register_app(ws: &mut Ws, payload: HappInput) -> Result<Pres
 
     Ok(happ)
 }
+
+/// Updates happ `id` published by this holoport's agent in hha
+pub async fn handle_update_app(
+    ws: &mut Ws,
+    id: ActionHashB64,
+    payload: HappInput,
+) -> Result<PresentedHappBundle> {
+    let maybe_pubkey = ws.host_pub_key.clone();
+    let app_connection = ws.get_connection(ws.core_app_id.clone()).await?;
+
+    let happ: PresentedHappBundle = get_app_details(app_connection, id.clone().into()).await?;
+    let publisher = AgentPubKeyB64::from(get_host_pub_key(maybe_pubkey, app_connection).await?);
+    if happ.provider_pubkey != publisher {
+        return Err(anyhow!(
+            "Happ {} was published by {}, it can not be updated from this holoport",
+            id,
+            happ.provider_pubkey
+        ));
+    }
+
+    let payload = UpdateHappInput {
+        happ_id: id,
+        updated_happ: payload,
+    };
+    log::debug!("calling zome hha/update_happ with payload: {:?}", &payload);
+    let happ = app_connection
+        .zome_call_typed(
+            CoreAppRoleName::HHA.into(),
+            "hha".into(),
+            "update_happ".into(),
+            payload,
+        )
+        .await?;
+
+    Ok(happ)
+}
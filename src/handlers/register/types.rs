@@ -1,9 +1,14 @@
 use anyhow::anyhow;
+use holochain_types::{
+    dna::ActionHashB64,
+    prelude::{holochain_serial, SerializedBytes},
+};
 use rocket::{
     data::{self, Data, FromData},
     http::Status,
     outcome::Outcome,
     request::Request,
+    serde::{Deserialize, Serialize},
 };
 
 use crate::common::types::HappInput;
@@ -15,16 +20,28 @@ impl<'r> FromData<'r> for HappInput {
     async fn from_data(_request: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
         let byte_unit_data = data.open(data::ByteUnit::max_value());
         let decoded_data = byte_unit_data.into_bytes().await.unwrap();
-        let register_payload: HappInput = match rocket::serde::json::serde_json::from_slice(&decoded_data.value) {
-            Ok(payload) => payload,
-            Err(e) => {
-                return Outcome::Error((
-                    Status::UnprocessableEntity,
-                    anyhow!("Provided payload to `apps/hosted/register` does not match expected payload. Error: {:?}", e),
-                ))
-            }
-        };
+        let register_payload: HappInput =
+            match rocket::serde::json::serde_json::from_slice(&decoded_data.value) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    return Outcome::Error((
+                        Status::UnprocessableEntity,
+                        anyhow!(
+                            "Provided happ payload does not match expected payload. Error: {:?}",
+                            e
+                        ),
+                    ))
+                }
+            };
 
         Outcome::Success(register_payload)
     }
 }
+
+// input of zome call hha/update_happ
+#[derive(Debug, Serialize, Deserialize, SerializedBytes)]
+#[serde(crate = "rocket::serde")]
+pub struct UpdateHappInput {
+    pub happ_id: ActionHashB64,
+    pub updated_happ: HappInput,
+}
@@ -0,0 +1,240 @@
+// Validation of happ registration and update before it is sent to hha, which stores whatever it is given.
+// All problems found are reported at once, so that publisher can fix them in a single round.
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use holochain_types::dna::DnaHashB64;
+use holofuel_types::fuel::Fuel;
+use log::debug;
+use reqwest::{Client, StatusCode};
+use url::Url;
+
+use crate::common::types::HappInput;
+
+/// ISO 3166-1 alpha-2 codes
+const JURISDICTION_CODES: [&str; 249] = [
+    "AD", "AE", "AF", "AG", "AI", "AL", "AM", "AO", "AQ", "AR", "AS", "AT", "AU", "AW", "AX", "AZ",
+    "BA", "BB", "BD", "BE", "BF", "BG", "BH", "BI", "BJ", "BL", "BM", "BN", "BO", "BQ", "BR", "BS",
+    "BT", "BV", "BW", "BY", "BZ", "CA", "CC", "CD", "CF", "CG", "CH", "CI", "CK", "CL", "CM", "CN",
+    "CO", "CR", "CU", "CV", "CW", "CX", "CY", "CZ", "DE", "DJ", "DK", "DM", "DO", "DZ", "EC", "EE",
+    "EG", "EH", "ER", "ES", "ET", "FI", "FJ", "FK", "FM", "FO", "FR", "GA", "GB", "GD", "GE", "GF",
+    "GG", "GH", "GI", "GL", "GM", "GN", "GP", "GQ", "GR", "GS", "GT", "GU", "GW", "GY", "HK", "HM",
+    "HN", "HR", "HT", "HU", "ID", "IE", "IL", "IM", "IN", "IO", "IQ", "IR", "IS", "IT", "JE", "JM",
+    "JO", "JP", "KE", "KG", "KH", "KI", "KM", "KN", "KP", "KR", "KW", "KY", "KZ", "LA", "LB", "LC",
+    "LI", "LK", "LR", "LS", "LT", "LU", "LV", "LY", "MA", "MC", "MD", "ME", "MF", "MG", "MH", "MK",
+    "ML", "MM", "MN", "MO", "MP", "MQ", "MR", "MS", "MT", "MU", "MV", "MW", "MX", "MY", "MZ", "NA",
+    "NC", "NE", "NF", "NG", "NI", "NL", "NO", "NP", "NR", "NU", "NZ", "OM", "PA", "PE", "PF", "PG",
+    "PH", "PK", "PL", "PM", "PN", "PR", "PS", "PT", "PW", "PY", "QA", "RE", "RO", "RS", "RU", "RW",
+    "SA", "SB", "SC", "SD", "SE", "SG", "SH", "SI", "SJ", "SK", "SL", "SM", "SN", "SO", "SR", "SS",
+    "ST", "SV", "SX", "SY", "SZ", "TC", "TD", "TF", "TG", "TH", "TJ", "TK", "TL", "TM", "TN", "TO",
+    "TR", "TT", "TV", "TW", "TZ", "UA", "UG", "UM", "US", "UY", "UZ", "VA", "VC", "VE", "VG", "VI",
+    "VN", "VU", "WF", "WS", "YE", "YT", "ZA", "ZM", "ZW",
+];
+
+/// Validates happ registration, including reachability of its urls
+pub async fn validate_happ_input(input: &HappInput) -> Result<()> {
+    let mut problems = check_happ_input(input);
+    if problems.is_empty() {
+        problems.extend(check_urls_reachable(input).await);
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!("Invalid happ: {}", problems.join("; ")))
+    }
+}
+
+/// Checks format of all fields of happ registration, returns list of problems found
+fn check_happ_input(input: &HappInput) -> Vec<String> {
+    let mut problems = vec![];
+
+    if input.name.trim().is_empty() {
+        problems.push("name is empty".to_string());
+    }
+
+    if input.bundle_url.is_empty() {
+        problems.push("bundle_url is empty".to_string());
+    } else if let Err(e) = parse_url(&input.bundle_url, &["http", "https"]) {
+        problems.push(format!("bundle_url {}", e));
+    }
+    for (name, url) in [
+        ("ui_src_url", &input.ui_src_url),
+        ("logo_url", &input.logo_url),
+    ] {
+        if let Some(url) = url.as_ref().filter(|url| !url.is_empty()) {
+            if let Err(e) = parse_url(url, &["http", "https"]) {
+                problems.push(format!("{} {}", name, e));
+            }
+        }
+    }
+
+    for hosted_url in input.hosted_urls.iter() {
+        if !is_hostname(hosted_url) {
+            problems.push(format!(
+                "hosted url {} is not a valid host name",
+                hosted_url
+            ));
+        }
+    }
+
+    for dna in input.dnas.iter() {
+        if DnaHashB64::from_b64_str(&dna.hash).is_err() {
+            problems.push(format!(
+                "hash {} of dna {} is not a valid dna hash",
+                dna.hash, dna.nick
+            ));
+        }
+    }
+
+    for jurisdiction in input.jurisdictions.iter() {
+        if !JURISDICTION_CODES.contains(&jurisdiction.as_str()) {
+            problems.push(format!(
+                "jurisdiction {} is not an ISO 3166-1 alpha-2 code",
+                jurisdiction
+            ));
+        }
+    }
+
+    let zero = Fuel::new(0);
+    let pricing = &input.publisher_pricing_pref;
+    for (name, price) in [
+        ("cpu", pricing.cpu),
+        ("storage", pricing.storage),
+        ("bandwidth", pricing.bandwidth),
+    ] {
+        if price < zero {
+            problems.push(format!("{} price can not be negative", name));
+        }
+    }
+
+    problems
+}
+
+fn parse_url(url: &str, schemes: &[&str]) -> Result<Url> {
+    let parsed = Url::parse(url).map_err(|e| anyhow!("{} is not a valid url: {}", url, e))?;
+    if !schemes.contains(&parsed.scheme()) {
+        return Err(anyhow!(
+            "{} has to be one of {} urls",
+            url,
+            schemes.join(", ")
+        ));
+    }
+    Ok(parsed)
+}
+
+/// Hosted urls are host names under which happ's UI is served, e.g. `my-happ.holo.host`
+fn is_hostname(hostname: &str) -> bool {
+    !hostname.is_empty()
+        && hostname.len() <= 253
+        && hostname.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+async fn check_urls_reachable(input: &HappInput) -> Vec<String> {
+    let client = match Client::builder().timeout(Duration::from_secs(10)).build() {
+        Ok(client) => client,
+        Err(e) => return vec![format!("failed to check urls: {}", e)],
+    };
+
+    let mut problems = vec![];
+    for (name, url) in [
+        ("bundle_url", Some(&input.bundle_url)),
+        ("ui_src_url", input.ui_src_url.as_ref()),
+        ("logo_url", input.logo_url.as_ref()),
+    ] {
+        let url = match url.filter(|url| !url.is_empty()).map(|url| Url::parse(url)) {
+            Some(Ok(url)) => url,
+            _ => continue,
+        };
+        if let Err(e) = check_url_reachable(&client, &url).await {
+            problems.push(format!("{} {} is not reachable: {}", name, url, e));
+        }
+    }
+    problems
+}
+
+async fn check_url_reachable(client: &Client, url: &Url) -> Result<()> {
+    debug!("checking reachability of {}", url);
+    let mut status = client.head(url.clone()).send().await?.status();
+    // Not every server answers HEAD requests
+    if status == StatusCode::METHOD_NOT_ALLOWED {
+        status = client.get(url.clone()).send().await?.status();
+    }
+    if status.is_client_error() || status.is_server_error() {
+        return Err(anyhow!("server responded with {}", status));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use holofuel_types::fuel::Fuel;
+
+    use super::{check_happ_input, is_hostname};
+    use crate::common::types::{DnaResource, HappInput};
+
+    fn valid_input() -> HappInput {
+        HappInput {
+            name: "Test Happ".to_string(),
+            bundle_url: "https://example.com/test.happ".to_string(),
+            logo_url: Some("https://example.com/logo.png".to_string()),
+            hosted_urls: vec!["test-happ.holo.host".to_string()],
+            dnas: vec![DnaResource {
+                hash: "uhC0kGNBsMPAi8Amjsa5tEVsRHZWaK-E7Fl8kLvuBvNuYtfuG1gkP".to_string(),
+                src_url: "test.dna".to_string(),
+                nick: "test".to_string(),
+            }],
+            jurisdictions: vec!["DE".to_string(), "US".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn accepts_valid_happ_input() {
+        assert!(check_happ_input(&valid_input()).is_empty());
+    }
+
+    #[test]
+    fn reports_all_problems_of_happ_input() {
+        let mut input = valid_input();
+        input.bundle_url = "Url123".to_string();
+        input.logo_url = Some("ftp://example.com/logo.png".to_string());
+        input.hosted_urls = vec!["test_happ_host_url".to_string()];
+        input.dnas[0].hash = "uhC0k123".to_string();
+        input.jurisdictions = vec!["Germany".to_string()];
+        input.publisher_pricing_pref.storage = Fuel::from_str("-1").unwrap();
+
+        let problems = check_happ_input(&input);
+        assert_eq!(problems.len(), 6, "{:?}", problems);
+    }
+
+    #[test]
+    fn rejects_local_bundle_urls() {
+        // a bundle on publisher's holoport can not be downloaded by other hosts
+        let mut input = valid_input();
+        input.bundle_url = "file:///etc/passwd".to_string();
+
+        let problems = check_happ_input(&input);
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert!(problems[0].starts_with("bundle_url"));
+    }
+
+    #[test]
+    fn validates_host_names() {
+        assert!(is_hostname("my-happ.holo.host"));
+        assert!(is_hostname("localhost"));
+        assert!(!is_hostname("https://my-happ.holo.host"));
+        assert!(!is_hostname("-my-happ.holo.host"));
+        assert!(!is_hostname("my..happ"));
+        assert!(!is_hostname(""));
+    }
+}
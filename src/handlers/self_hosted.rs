@@ -0,0 +1,302 @@
+/*
+Self-hosted Happs Overview
+
+Self-hosted happs are happs the host runs for themselves. They are not registered in hha, so they have no
+`bundle_url` to download from and no servicelogger instance. They are installed from a bundle uploaded
+to the API or from a path on the holoport, under host's agent key. Bundles installed from a path have to be
+`.happ` files inside `HPOS_API_DATA_DIR`, so that the API can not be used to read arbitrary files of the holoport.
+
+Apps installed this way are recorded in `<HPOS_API_DATA_DIR>/self_hosted.json` together with their source,
+their status is always read from the conductor. `installed_app_id` of a self-hosted happ can not contain `:`,
+which is reserved for instances of hosted happs (`<happ_id>:<agent>` and `<happ_id>::servicelogger`).
+*/
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use holochain_conductor_api::AppInfoStatus;
+use holochain_types::{
+    dna::ActionHashB64,
+    prelude::{AppBundleSource, MembraneProof, RoleName, Timestamp},
+};
+use log::debug;
+use rocket::serde::{Deserialize, Serialize};
+
+use super::install::{
+    get_host_pub_key, handle_holochain_disable, handle_holochain_enable,
+    handle_holochain_uninstall, handle_install_app_raw, RawInstallAppPayload,
+    SuccessfulInstallResult,
+};
+use crate::common::store::{load_json, save_json};
+use crate::hpos::{get_data_dir, Ws};
+
+pub struct SelfHostedInstall {
+    pub installed_app_id: String,
+    pub bundle_path: PathBuf,
+    pub source: String, // file name of uploaded bundle or path it was installed from
+    pub network_seed: Option<String>,
+    pub membrane_proofs: HashMap<RoleName, MembraneProof>,
+}
+
+/// Installs and enables a self-hosted happ under host's agent key
+pub async fn handle_install_self_hosted(
+    ws: &mut Ws,
+    payload: SelfHostedInstall,
+) -> Result<SelfHostedHapp> {
+    validate_installed_app_id(&payload.installed_app_id, &ws.core_app_id)?;
+
+    let maybe_pubkey = ws.host_pub_key.clone();
+    let core_app_connection = ws.get_connection(ws.core_app_id.clone()).await?;
+    let agent_key = get_host_pub_key(maybe_pubkey, core_app_connection).await?;
+
+    debug!(
+        "installing self-hosted happ {} from {}",
+        payload.installed_app_id, payload.source
+    );
+    let raw_payload = RawInstallAppPayload {
+        source: AppBundleSource::Path(payload.bundle_path),
+        agent_key,
+        installed_app_id: payload.installed_app_id.clone(),
+        membrane_proofs: payload.membrane_proofs,
+        existing_cells: HashMap::new(),
+        uid: payload.network_seed.clone(),
+    };
+    if let SuccessfulInstallResult::AlreadyInstalled =
+        handle_install_app_raw(&mut ws.admin, raw_payload).await?
+    {
+        return Err(anyhow!(
+            "App {} is already installed",
+            payload.installed_app_id
+        ));
+    }
+
+    let record = SelfHostedRecord {
+        installed_app_id: payload.installed_app_id.clone(),
+        source: payload.source,
+        network_seed: payload.network_seed,
+        installed_at: Timestamp::now(),
+    };
+    let mut registry = load_registry()?;
+    registry
+        .happs
+        .retain(|happ| happ.installed_app_id != record.installed_app_id);
+    registry.happs.push(record.clone());
+    save_registry(&registry)?;
+
+    let info = handle_holochain_enable(&mut ws.admin, &payload.installed_app_id).await?;
+
+    Ok(SelfHostedHapp::new(record, Some(info.status)))
+}
+
+/// Lists self-hosted happs installed through this API with their status in the conductor
+pub async fn handle_list_self_hosted(ws: &mut Ws) -> Result<Vec<SelfHostedHapp>> {
+    let mut statuses: HashMap<String, AppInfoStatus> = ws
+        .admin
+        .list_apps(None)
+        .await
+        .map_err(|err| anyhow!("{:?}", err))?
+        .into_iter()
+        .map(|info| (info.installed_app_id, info.status))
+        .collect();
+
+    Ok(load_registry()?
+        .happs
+        .into_iter()
+        .map(|record| {
+            let status = statuses.remove(&record.installed_app_id);
+            SelfHostedHapp::new(record, status)
+        })
+        .collect())
+}
+
+pub async fn handle_enable_self_hosted(ws: &mut Ws, installed_app_id: &str) -> Result<()> {
+    let installed_app_id = get_registered_id(installed_app_id)?;
+    handle_holochain_enable(&mut ws.admin, &installed_app_id).await?;
+    Ok(())
+}
+
+pub async fn handle_disable_self_hosted(ws: &mut Ws, installed_app_id: &str) -> Result<()> {
+    let installed_app_id = get_registered_id(installed_app_id)?;
+    handle_holochain_disable(&mut ws.admin, &installed_app_id).await?;
+    ws.apps.remove(&installed_app_id);
+    Ok(())
+}
+
+/// Uninstalls self-hosted happ together with its data and removes it from the registry
+pub async fn handle_remove_self_hosted(ws: &mut Ws, installed_app_id: &str) -> Result<()> {
+    let installed_app_id = get_registered_id(installed_app_id)?;
+
+    let installed = ws
+        .admin
+        .list_apps(None)
+        .await
+        .map_err(|err| anyhow!("{:?}", err))?
+        .iter()
+        .any(|info| info.installed_app_id == installed_app_id);
+    if installed {
+        handle_holochain_uninstall(&mut ws.admin, &installed_app_id).await?;
+        ws.apps.remove(&installed_app_id);
+    }
+
+    let mut registry = load_registry()?;
+    registry
+        .happs
+        .retain(|happ| happ.installed_app_id != installed_app_id);
+    save_registry(&registry)
+}
+
+/// Resolves `path` of a bundle to install to a `.happ` file inside `allowed_dir`.
+/// Symlinks and `..` are resolved first, so that they can not point outside of `allowed_dir`.
+pub fn resolve_bundle_path(path: &str, allowed_dir: &Path) -> Result<PathBuf> {
+    let allowed_dir = allowed_dir
+        .canonicalize()
+        .map_err(|e| anyhow!("Directory {:?} can not be read: {}", allowed_dir, e))?;
+    let bundle_path = Path::new(path)
+        .canonicalize()
+        .map_err(|e| anyhow!("File {} can not be read: {}", path, e))?;
+
+    if !bundle_path.starts_with(&allowed_dir) {
+        return Err(anyhow!("File {} is not inside {:?}", path, allowed_dir));
+    }
+    if !bundle_path.is_file() || bundle_path.extension().map_or(true, |ext| ext != "happ") {
+        return Err(anyhow!("File {} is not a .happ bundle", path));
+    }
+    Ok(bundle_path)
+}
+
+/// Ids containing `:` belong to instances of hosted happs, ids that are action hashes to hosted happs themselves
+pub fn validate_installed_app_id(installed_app_id: &str, core_app_id: &str) -> Result<()> {
+    if installed_app_id.trim().is_empty() {
+        return Err(anyhow!("installed_app_id can not be empty"));
+    }
+    if installed_app_id.contains(':') {
+        return Err(anyhow!(
+            "installed_app_id {} can not contain `:`",
+            installed_app_id
+        ));
+    }
+    if ActionHashB64::from_b64_str(installed_app_id).is_ok() {
+        return Err(anyhow!(
+            "installed_app_id {} is reserved for a hosted happ",
+            installed_app_id
+        ));
+    }
+    if installed_app_id == core_app_id {
+        return Err(anyhow!(
+            "installed_app_id {} is reserved for core app",
+            installed_app_id
+        ));
+    }
+    Ok(())
+}
+
+/// Only self-hosted happs can be managed through self-hosted endpoints
+fn get_registered_id(installed_app_id: &str) -> Result<String> {
+    load_registry()?
+        .happs
+        .into_iter()
+        .find(|happ| happ.installed_app_id == installed_app_id)
+        .map(|happ| happ.installed_app_id)
+        .ok_or_else(|| anyhow!("{} is not a self-hosted happ", installed_app_id))
+}
+
+/// Directory for bundles uploaded for installation, they are removed once installed
+pub fn get_upload_dir() -> PathBuf {
+    get_data_dir().join("self_hosted")
+}
+
+fn load_registry() -> Result<SelfHostedRegistry> {
+    load_json(&get_data_dir().join("self_hosted.json"))
+}
+
+fn save_registry(registry: &SelfHostedRegistry) -> Result<()> {
+    save_json(&get_data_dir().join("self_hosted.json"), registry)
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(crate = "rocket::serde")]
+struct SelfHostedRegistry {
+    happs: Vec<SelfHostedRecord>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+struct SelfHostedRecord {
+    installed_app_id: String,
+    source: String,
+    network_seed: Option<String>,
+    installed_at: Timestamp,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct SelfHostedHapp {
+    pub installed_app_id: String,
+    pub source: String,
+    pub network_seed: Option<String>,
+    pub installed_at: Timestamp,
+    pub status: Option<AppInfoStatus>, // null when app has been uninstalled from conductor by other means
+}
+
+impl SelfHostedHapp {
+    fn new(record: SelfHostedRecord, status: Option<AppInfoStatus>) -> Self {
+        SelfHostedHapp {
+            installed_app_id: record.installed_app_id,
+            source: record.source,
+            network_seed: record.network_seed,
+            installed_at: record.installed_at,
+            status,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, fs};
+
+    use super::{resolve_bundle_path, validate_installed_app_id};
+
+    #[test]
+    fn validates_installed_app_id() {
+        let core_app_id = "core-app";
+
+        assert!(validate_installed_app_id("my-notes", core_app_id).is_ok());
+        assert!(validate_installed_app_id("", core_app_id).is_err());
+        assert!(validate_installed_app_id("my-notes::servicelogger", core_app_id).is_err());
+        assert!(validate_installed_app_id(
+            "uhCkklkJVx4u17eCaaKg_phRJsHOj9u57v_4cHQR-Bd9tb-vePRyC",
+            core_app_id
+        )
+        .is_err());
+        assert!(validate_installed_app_id(core_app_id, core_app_id).is_err());
+    }
+
+    #[test]
+    fn resolves_bundle_paths_inside_allowed_dir() {
+        let root = env::temp_dir().join("hpos-api-rust-test-self-hosted");
+        let allowed_dir = root.join("data");
+        fs::create_dir_all(&allowed_dir).unwrap();
+        let bundle = allowed_dir.join("notes.happ");
+        fs::write(&bundle, b"bundle").unwrap();
+        fs::write(allowed_dir.join("notes.json"), b"{}").unwrap();
+        fs::write(root.join("outside.happ"), b"bundle").unwrap();
+
+        let resolve = |path: &str| resolve_bundle_path(path, &allowed_dir);
+
+        assert_eq!(
+            resolve(bundle.to_str().unwrap()).unwrap(),
+            bundle.canonicalize().unwrap()
+        );
+        // `..` can not escape allowed dir
+        let escaping = allowed_dir.join("..").join("outside.happ");
+        assert!(resolve(escaping.to_str().unwrap()).is_err());
+        assert!(resolve(root.join("outside.happ").to_str().unwrap()).is_err());
+        assert!(resolve(allowed_dir.join("notes.json").to_str().unwrap()).is_err());
+        assert!(resolve(allowed_dir.join("missing.happ").to_str().unwrap()).is_err());
+        assert!(resolve(allowed_dir.to_str().unwrap()).is_err());
+        assert!(resolve("/etc/passwd").is_err());
+    }
+}
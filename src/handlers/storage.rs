@@ -0,0 +1,275 @@
+// Disk usage of a hosted happ as reported by servicelogger's disk usage logs.
+// Each log is a snapshot of sizes of all database files of happ's DNAs taken at the time of logging.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use holochain_types::{
+    dna::{ActionHashB64, DnaHashB64},
+    prelude::{ActionType, ChainQueryFilter, Timestamp},
+};
+use rocket::serde::{Deserialize, Serialize};
+
+use super::hosted_happs::{query_service_logs, to_log_entry, DiskUsageLog, File, LogEntry};
+use crate::common::series::timestamp_to_date_time;
+use crate::hpos::Ws;
+
+/// Returns the latest disk usage snapshot of happ `id` broken down per DNA and per file type,
+/// together with the trend of total disk usage over snapshots taken since `since`
+pub async fn handle_get_storage(
+    ws: &mut Ws,
+    id: ActionHashB64,
+    since: DateTime<Utc>,
+) -> Result<StorageReport> {
+    let filter = ChainQueryFilter::new()
+        .include_entries(true)
+        .action_type(ActionType::Create);
+    let records = query_service_logs(ws, &id, filter).await?;
+
+    // chain is returned in ascending order, so are snapshots
+    let snapshots: Vec<(Timestamp, DiskUsageLog)> = records
+        .iter()
+        .filter_map(|record| match to_log_entry(record) {
+            Some(LogEntry::DiskUsageLog(log)) => Some((record.action().timestamp(), log)),
+            _ => None,
+        })
+        .collect();
+
+    let latest = snapshots.last().map(|(timestamp, log)| StorageSnapshot {
+        timestamp: *timestamp,
+        source_chain_count: log.source_chain_count,
+        total: sum_by_file_type(log.files.iter()),
+        by_dna: group_by_dna(&log.files),
+    });
+
+    let points: Vec<StoragePoint> = snapshots
+        .iter()
+        .filter(|(timestamp, _)| timestamp_to_date_time(*timestamp) >= since)
+        .map(|(timestamp, log)| StoragePoint {
+            timestamp: *timestamp,
+            total: log.files.iter().map(|file| file.size).sum(),
+        })
+        .collect();
+
+    Ok(StorageReport {
+        happ_id: id,
+        latest,
+        trend: StorageTrend::from_points(points),
+    })
+}
+
+#[derive(Debug, PartialEq)]
+enum FileType {
+    Sqlite,
+    Shm,
+    Wal,
+    Other,
+}
+
+/// Classifies a database file by its extension, typically .sqlite3, .sqlite3-shm, or .sqlite3-wal
+fn file_type(extension: &str) -> FileType {
+    if extension.ends_with("-shm") {
+        FileType::Shm
+    } else if extension.ends_with("-wal") {
+        FileType::Wal
+    } else if extension.trim_start_matches('.').starts_with("sqlite") {
+        FileType::Sqlite
+    } else {
+        FileType::Other
+    }
+}
+
+fn sum_by_file_type<'a>(files: impl Iterator<Item = &'a File>) -> FileTypeSizes {
+    let mut sizes = FileTypeSizes::default();
+    for file in files {
+        sizes.total += file.size;
+        match file_type(&file.extension) {
+            FileType::Sqlite => sizes.sqlite += file.size,
+            FileType::Shm => sizes.shm += file.size,
+            FileType::Wal => sizes.wal += file.size,
+            FileType::Other => sizes.other += file.size,
+        }
+    }
+    sizes
+}
+
+/// Groups files by associated DNA, largest DNAs first
+fn group_by_dna(files: &[File]) -> Vec<DnaStorage> {
+    let mut dnas: BTreeMap<String, Vec<&File>> = BTreeMap::new();
+    for file in files {
+        dnas.entry(file.associated_dna.to_string())
+            .or_default()
+            .push(file);
+    }
+
+    let mut result: Vec<DnaStorage> = dnas
+        .into_values()
+        .map(|files| DnaStorage {
+            dna: files[0].associated_dna.clone(),
+            sizes: sum_by_file_type(files.into_iter()),
+        })
+        .collect();
+    result.sort_by(|a, b| b.sizes.total.cmp(&a.sizes.total));
+    result
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct StorageReport {
+    pub happ_id: ActionHashB64,
+    pub latest: Option<StorageSnapshot>, // null when happ has no disk usage log yet
+    pub trend: StorageTrend,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct StorageSnapshot {
+    pub timestamp: Timestamp,
+    pub source_chain_count: u32,
+    pub total: FileTypeSizes,
+    pub by_dna: Vec<DnaStorage>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct DnaStorage {
+    pub dna: DnaHashB64,
+    pub sizes: FileTypeSizes,
+}
+
+/// Sizes in bytes
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct FileTypeSizes {
+    pub total: u64,
+    pub sqlite: u64,
+    pub shm: u64,
+    pub wal: u64,
+    pub other: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct StoragePoint {
+    pub timestamp: Timestamp,
+    pub total: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct StorageTrend {
+    pub change: i64, // bytes between the first and the last snapshot in range
+    pub bytes_per_day: Option<i64>, // null when there are less than 2 snapshots in range
+    pub points: Vec<StoragePoint>,
+}
+
+impl StorageTrend {
+    fn from_points(points: Vec<StoragePoint>) -> Self {
+        let (first, last) = match (points.first(), points.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return StorageTrend::default(),
+        };
+
+        let change = last.total as i64 - first.total as i64;
+        let elapsed_micros = last.timestamp.as_micros() - first.timestamp.as_micros();
+        let bytes_per_day = if elapsed_micros > 0 {
+            let day_micros = 24 * 60 * 60 * 1_000_000_i128;
+            Some((change as i128 * day_micros / elapsed_micros as i128) as i64)
+        } else {
+            None
+        };
+
+        StorageTrend {
+            change,
+            bytes_per_day,
+            points,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use holochain_types::{dna::DnaHash, prelude::Timestamp};
+
+    use super::*;
+
+    fn file(dna: u8, extension: &str, size: u64) -> File {
+        File {
+            associated_dna: DnaHash::from_raw_36(vec![dna; 36]).into(),
+            extension: extension.to_string(),
+            size,
+        }
+    }
+
+    #[test]
+    fn classifies_files_by_extension() {
+        assert_eq!(file_type(".sqlite3"), FileType::Sqlite);
+        assert_eq!(file_type("sqlite3"), FileType::Sqlite);
+        assert_eq!(file_type(".sqlite3-shm"), FileType::Shm);
+        assert_eq!(file_type(".sqlite3-wal"), FileType::Wal);
+        assert_eq!(file_type(".json"), FileType::Other);
+    }
+
+    #[test]
+    fn groups_files_by_dna() {
+        let files = vec![
+            file(1, ".sqlite3", 100),
+            file(1, ".sqlite3-wal", 10),
+            file(2, ".sqlite3", 500),
+            file(2, ".sqlite3-shm", 5),
+        ];
+
+        let by_dna = group_by_dna(&files);
+        assert_eq!(by_dna.len(), 2);
+        assert_eq!(
+            by_dna[0].dna,
+            DnaHashB64::from(DnaHash::from_raw_36(vec![2; 36]))
+        );
+        assert_eq!(
+            by_dna[0].sizes,
+            FileTypeSizes {
+                total: 505,
+                sqlite: 500,
+                shm: 5,
+                wal: 0,
+                other: 0
+            }
+        );
+        assert_eq!(by_dna[1].sizes.wal, 10);
+        assert_eq!(sum_by_file_type(files.iter()).total, 615);
+    }
+
+    #[test]
+    fn computes_storage_trend() {
+        let day = 24 * 60 * 60 * 1_000_000;
+        let points = vec![
+            StoragePoint {
+                timestamp: Timestamp::from_micros(0),
+                total: 1000,
+            },
+            StoragePoint {
+                timestamp: Timestamp::from_micros(day),
+                total: 1500,
+            },
+            StoragePoint {
+                timestamp: Timestamp::from_micros(2 * day),
+                total: 3000,
+            },
+        ];
+
+        let trend = StorageTrend::from_points(points);
+        assert_eq!(trend.change, 2000);
+        assert_eq!(trend.bytes_per_day, Some(1000));
+
+        let trend = StorageTrend::from_points(vec![]);
+        assert_eq!(trend.change, 0);
+        assert_eq!(trend.bytes_per_day, None);
+    }
+}
@@ -0,0 +1,125 @@
+/*
+Hosted Happ Uninstallation Overview
+
+A hosted happ occupies the following on a holoport:
+- its record in hha, where it is marked as enabled by the host
+- `<happ_id>` - the hosted happ instance installed by `handle_install_app` (absent for happs with `special_installed_app_id`)
+- `<happ_id>:uhCA...` - one instance per hosted web agent
+- `<happ_id>::servicelogger` - servicelogger instance assigned to the hosted happ
+- bundle of the happ in the bundle cache, and the bundle its instance was installed from, see `/apps/bundles`
+
+Uninstallation disables the happ in hha first so that no new agents are assigned to this holoport,
+then removes all of the above. In `keep_data` mode nothing is removed and the report only lists what would be.
+Cached bundles that also serve another happ published in hha, or the base servicelogger, are kept.
+*/
+
+use anyhow::{anyhow, Result};
+use holochain_types::dna::ActionHashB64;
+use log::debug;
+use rocket::serde::{Deserialize, Serialize};
+
+use super::bundles::{self, CachedBundle};
+use super::hosted_happs::{get_all_happs, handle_disable};
+use super::install::{get_sl_id, handle_holochain_uninstall};
+use crate::common::types::HappAndHost;
+use crate::hpos::Ws;
+
+pub async fn handle_uninstall_app(
+    ws: &mut Ws,
+    happ_id: ActionHashB64,
+    keep_data: bool,
+) -> Result<UninstallReport> {
+    let id = happ_id.to_string();
+
+    let installed_app_ids: Vec<String> = ws
+        .admin
+        .list_apps(None)
+        .await
+        .map_err(|err| anyhow!("{:?}", err))?
+        .into_iter()
+        .map(|info| info.installed_app_id)
+        .filter(|installed_app_id| belongs_to_happ(installed_app_id, &id))
+        .collect();
+
+    // Bundle urls of other happs are kept, their bundles may be shared with this one
+    let mut urls = vec![];
+    let mut used_urls = vec![];
+    for happ in get_all_happs(ws).await? {
+        if happ.id == happ_id {
+            urls.push(happ.bundle_url);
+        } else {
+            used_urls.push(happ.bundle_url);
+        }
+    }
+    if let Some(url) = &ws.base_sl.bundle_url {
+        used_urls.push(url.to_string());
+    }
+
+    if !keep_data {
+        debug!("disabling happ {} in hha before uninstalling it", &id);
+        let payload = HappAndHost::init(&id).await?;
+        handle_disable(ws, payload).await?;
+
+        for installed_app_id in installed_app_ids.iter() {
+            debug!("uninstalling app {}", installed_app_id);
+            handle_holochain_uninstall(&mut ws.admin, installed_app_id).await?;
+            // Drop cached app connection, it points to an app that no longer exists
+            ws.apps.remove(installed_app_id);
+        }
+    }
+
+    let bundles = bundles::remove_unused(&id, &urls, &used_urls, keep_data)?;
+
+    Ok(UninstallReport {
+        happ_id,
+        dry_run: keep_data,
+        installed_app_ids,
+        bundles,
+    })
+}
+
+/// Checks if `installed_app_id` follows one of the naming conventions of apps installed for hosted happ `happ_id`
+fn belongs_to_happ(installed_app_id: &str, happ_id: &String) -> bool {
+    installed_app_id == happ_id
+        || installed_app_id == get_sl_id(happ_id)
+        || installed_app_id.starts_with(&format!("{}:uhCA", happ_id))
+}
+
+/// Lists what was removed for a hosted happ, or what would be removed in `keep_data` mode
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct UninstallReport {
+    pub happ_id: ActionHashB64,
+    pub dry_run: bool,
+    pub installed_app_ids: Vec<String>,
+    pub bundles: Vec<CachedBundle>, // removed from bundle cache
+}
+
+#[cfg(test)]
+mod test {
+    use super::belongs_to_happ;
+
+    #[test]
+    fn matches_hosted_happ_app_ids() {
+        let happ_id = "uhCkklkJVx4u17eCaaKg_phRJsHOj9u57v_4cHQR-Bd9tb-vePRyC".to_string();
+
+        assert!(belongs_to_happ(&happ_id, &happ_id));
+        assert!(belongs_to_happ(
+            &format!("{}::servicelogger", happ_id),
+            &happ_id
+        ));
+        assert!(belongs_to_happ(
+            &format!(
+                "{}:uhCAkMdhGSO7W7ccCEd7UthPCiB37tNcO10MTEuBDIC5fS1MI2IsR",
+                happ_id
+            ),
+            &happ_id
+        ));
+        assert!(!belongs_to_happ("core-app:0_6_2", &happ_id));
+        assert!(!belongs_to_happ(
+            "uhCkkMpS5xUbci4IiBXpmlFCAJF3unOq-ZBkMrbJTsuiieTllOLtY::servicelogger",
+            &happ_id
+        ));
+    }
+}
@@ -0,0 +1,553 @@
+/*
+Hosted Happ Upgrade Overview
+
+A publisher can edit their happ in hha and point it to a new bundle, but instances installed on a holoport
+keep running the bundle that was downloaded at the time of installation. A happ is outdated
+when DNA hashes of its installed instance differ from `DnaResource.hash` values published in hha.
+
+Upgrade of an outdated happ:
+- finds the bundle the running instance was installed from (recorded in the bundle cache at installation),
+  to be able to roll back
+- downloads the new bundle into the bundle cache
+- installs the new version next to the running one as `<happ_id>::upgrade`, with the same agent key, network seed
+  and membrane proofs. Roles whose DNA has not changed use the cells of the running instance (`UseExisting`),
+  so their source chains are kept. Roles with a new DNA start new chains.
+- only once that succeeded, swaps instances: the running `<happ_id>` is uninstalled and installed again using
+  cells of `<happ_id>::upgrade`, which is then uninstalled. Cells that are still used by another app are not
+  removed by holochain on uninstall, so no chain of the new version is lost by the swap.
+- restores the enabled or disabled state of the running instance
+- leaves servicelogger instance `<happ_id>::servicelogger` untouched, so logs and invoicing continue
+
+When the new version fails to install or enable, the running instance is left untouched. When the swap fails,
+the previous version is installed again from its recorded bundle, keeping chains of roles whose DNA has not changed.
+Upgrade is refused when that bundle is no longer available.
+
+Instances of web agents `<happ_id>:uhCA...` are left running on the previous DNAs so that their chains are kept,
+they are listed in the report and can be removed with `DELETE /apps/hosted/<id>/agents/<agent>`.
+*/
+
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::{anyhow, Result};
+use holochain_client::{AgentPubKey, AppInfo};
+use holochain_conductor_api::{AppInfoStatus, CellInfo};
+use holochain_types::{
+    app::{AppManifest, CellProvisioning},
+    dna::{ActionHashB64, DnaHashB64},
+    prelude::{AppBundleSource, CellId, MembraneProof, RoleName, Timestamp},
+};
+use log::{debug, warn};
+use mr_bundle::Bundle;
+use rocket::serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::bundles::{self, get_bundle, BundleFile, InstalledBundle};
+use super::hosted_happs::get_all_happs;
+use super::install::{
+    find_unpublished_dna, get_app_details, get_bundle_dna_hashes, get_verified_bundle,
+    handle_holochain_enable, handle_holochain_uninstall, handle_install_app_raw,
+    RawInstallAppPayload, SuccessfulInstallResult,
+};
+use crate::common::types::{DnaResource, PresentedHappBundle};
+use crate::hpos::Ws;
+
+/// Lists happs installed on this holoport which run DNAs different from those published in hha
+pub async fn handle_get_outdated(ws: &mut Ws) -> Result<Vec<OutdatedHapp>> {
+    let happs = get_all_happs(ws).await?;
+    let installed_apps: HashMap<String, AppInfo> = ws
+        .admin
+        .list_apps(None)
+        .await
+        .map_err(|err| anyhow!("{:?}", err))?
+        .into_iter()
+        .map(|info| (info.installed_app_id.clone(), info))
+        .collect();
+
+    let mut result = vec![];
+    for happ in happs {
+        // Core happs are installed and upgraded together with HPOS
+        if happ.special_installed_app_id.is_some() {
+            continue;
+        }
+        let info = match installed_apps.get(&happ.id.to_string()) {
+            Some(info) => info,
+            None => continue,
+        };
+
+        let installed_dnas = get_role_dnas(info);
+        if is_outdated(&installed_dnas, &happ.dnas) {
+            result.push(OutdatedHapp::new(&happ, installed_dnas, &installed_apps));
+        }
+    }
+
+    Ok(result)
+}
+
+/// Reinstalls host's instance of happ `id` from the bundle currently published in hha
+pub async fn handle_upgrade_app(ws: &mut Ws, id: ActionHashB64) -> Result<UpgradeReport> {
+    let app_connection = ws.get_connection(ws.core_app_id.clone()).await?;
+    let happ: PresentedHappBundle = get_app_details(app_connection, id.clone().into()).await?;
+
+    if happ.special_installed_app_id.is_some() {
+        return Err(anyhow!(
+            "Happ {} is a core happ, it is upgraded together with HPOS",
+            id
+        ));
+    }
+
+    let installed_app_id = id.to_string();
+    let installed_apps = ws
+        .admin
+        .list_apps(None)
+        .await
+        .map_err(|err| anyhow!("{:?}", err))?;
+    let info = installed_apps
+        .iter()
+        .find(|info| info.installed_app_id == installed_app_id)
+        .ok_or_else(|| anyhow!("Happ {} is not installed on this holoport", id))?;
+
+    let previous_dnas = get_role_dnas(info);
+    let agent_instances = list_agent_instances(&installed_apps, &id);
+
+    if !is_outdated(&previous_dnas, &happ.dnas) {
+        debug!("happ {} is up to date, skipping upgrade", id);
+        return Ok(UpgradeReport {
+            happ_id: id,
+            upgraded: false,
+            kept_roles: previous_dnas
+                .iter()
+                .map(|dna| dna.role_name.clone())
+                .collect(),
+            current_dnas: previous_dnas.clone(),
+            previous_dnas,
+            agent_instances,
+        });
+    }
+
+    let install = bundles::get_install(&installed_app_id)?;
+    let previous_bundle = get_previous_bundle(&happ, install.as_ref(), &previous_dnas).await?;
+
+    debug!(
+        "downloading new bundle of happ {} from {}",
+        id, happ.bundle_url
+    );
+    let bundle = get_verified_bundle(&happ).await?;
+
+    // membrane proofs are unknown for apps installed before installs were recorded,
+    // a happ that requires them fails to install below and is left running its previous version
+    let instance = Instance {
+        agent_key: info.agent_pub_key.clone(),
+        membrane_proofs: install
+            .map(|install| install.membrane_proofs)
+            .unwrap_or_default(),
+        uid: happ.uid.clone(),
+        enabled: !matches!(info.status, AppInfoStatus::Disabled { .. }),
+    };
+    let running_cells = get_role_cells(info);
+    let new_roles = get_bundle_dna_hashes(&bundle.path, &happ.uid).await?;
+    let kept_cells = select_kept_cells(&running_cells, &new_roles);
+
+    let upgrade_id = format!("{}::upgrade", id);
+    debug!(
+        "installing new version of happ {} as {}, keeping roles {:?}",
+        id,
+        upgrade_id,
+        kept_cells.keys()
+    );
+    let upgraded = match install_version(ws, &upgrade_id, &bundle, &instance, &kept_cells).await {
+        Ok(info) => info,
+        Err(e) => {
+            remove_leftover(ws, &upgrade_id).await;
+            return Err(anyhow!(
+                "Failed to install new version of happ {}, running version is left untouched: {}",
+                id,
+                e
+            ));
+        }
+    };
+    let current_dnas = get_role_dnas(&upgraded);
+    let upgraded_cells = get_role_cells(&upgraded);
+
+    debug!("swapping running version of happ {} for the new one", id);
+    if let Err(e) = handle_holochain_uninstall(&mut ws.admin, &installed_app_id).await {
+        remove_leftover(ws, &upgrade_id).await;
+        return Err(anyhow!(
+            "Failed to upgrade happ {}, running version could not be uninstalled: {}",
+            id,
+            e
+        ));
+    }
+    ws.apps.remove(&installed_app_id);
+
+    let result = match install_version(ws, &installed_app_id, &bundle, &instance, &upgraded_cells)
+        .await
+    {
+        Ok(_) => {
+            bundles::record_install(
+                &installed_app_id,
+                &happ.bundle_url,
+                &bundle,
+                instance.membrane_proofs.clone(),
+            )?;
+            Ok(UpgradeReport {
+                happ_id: id.clone(),
+                upgraded: true,
+                kept_roles: kept_cells.keys().cloned().collect(),
+                previous_dnas,
+                current_dnas,
+                agent_instances,
+            })
+        }
+        Err(e) => {
+            warn!(
+                "Failed to swap new version of happ {} in, rolling back: {}",
+                id, e
+            );
+            remove_leftover(ws, &installed_app_id).await;
+            match install_version(ws, &installed_app_id, &previous_bundle, &instance, &kept_cells)
+                    .await
+                {
+                    Ok(_) => Err(anyhow!(
+                        "Failed to upgrade happ {}: {}. Previous version has been reinstalled",
+                        id,
+                        e
+                    )),
+                    Err(rollback_error) => Err(anyhow!(
+                        "Failed to upgrade happ {}: {}. Reinstalling previous version failed as well, new version is left installed as {}: {}",
+                        id,
+                        e,
+                        upgrade_id,
+                        rollback_error
+                    )),
+                }
+        }
+    };
+
+    // upgrade instance holds cells only until `<happ_id>` uses them
+    if result.is_ok() || is_installed(ws, &installed_app_id).await {
+        remove_leftover(ws, &upgrade_id).await;
+    }
+    result
+}
+
+/// What an instance of a happ is installed with, carried over to its new version
+struct Instance {
+    agent_key: AgentPubKey,
+    membrane_proofs: HashMap<RoleName, MembraneProof>,
+    uid: Option<String>,
+    enabled: bool,
+}
+
+/// Returns the bundle that the running instance of `happ` has been installed from
+async fn get_previous_bundle(
+    happ: &PresentedHappBundle,
+    install: Option<&InstalledBundle>,
+    previous_dnas: &[RoleDna],
+) -> Result<BundleFile> {
+    let bundle = match install {
+        Some(install) => bundles::get_installed_bundle(install).await?,
+        // installed before installs were recorded, its bundle may still be cached under the happ's url
+        None => Some(get_bundle(&Url::parse(&happ.bundle_url)?, false).await?),
+    };
+    let bundle = bundle.ok_or_else(|| {
+        anyhow!(
+            "Bundle of the running version of happ {} is not available, upgrade could not be rolled back if it failed",
+            happ.id
+        )
+    })?;
+
+    let installed: Vec<&str> = previous_dnas
+        .iter()
+        .map(|dna| dna.dna_hash.as_str())
+        .collect();
+    let roles = get_bundle_dna_hashes(&bundle.path, &happ.uid).await?;
+    if find_unpublished_dna(&roles, &installed).is_some() {
+        return Err(anyhow!(
+            "Bundle of the running version of happ {} is not cached, cached bundle at {} is another version. Upgrade could not be rolled back if it failed",
+            happ.id,
+            happ.bundle_url
+        ));
+    }
+    Ok(bundle)
+}
+
+/// Installs `installed_app_id` from `bundle` and enables it if `instance` was enabled.
+/// Roles in `existing_cells` use those cells instead of creating new ones, so that their chains are kept.
+async fn install_version(
+    ws: &mut Ws,
+    installed_app_id: &str,
+    bundle: &BundleFile,
+    instance: &Instance,
+    existing_cells: &HashMap<RoleName, CellId>,
+) -> Result<AppInfo> {
+    let payload = RawInstallAppPayload {
+        source: use_existing_cells(bundle, existing_cells).await?,
+        agent_key: instance.agent_key.clone(),
+        installed_app_id: installed_app_id.to_string(),
+        // roles using existing cells have passed genesis already
+        membrane_proofs: instance
+            .membrane_proofs
+            .iter()
+            .filter(|(role_name, _)| !existing_cells.contains_key(*role_name))
+            .map(|(role_name, proof)| (role_name.clone(), proof.clone()))
+            .collect(),
+        existing_cells: existing_cells.clone(),
+        uid: instance.uid.clone(),
+    };
+    let info = match handle_install_app_raw(&mut ws.admin, payload).await? {
+        SuccessfulInstallResult::New(info) => info,
+        SuccessfulInstallResult::AlreadyInstalled => {
+            return Err(anyhow!("App {} is already installed", installed_app_id))
+        }
+    };
+
+    if instance.enabled {
+        handle_holochain_enable(&mut ws.admin, &installed_app_id.to_string()).await
+    } else {
+        Ok(info)
+    }
+}
+
+/// Returns source of `bundle` whose roles in `existing_cells` are provisioned with existing cells
+async fn use_existing_cells(
+    bundle: &BundleFile,
+    existing_cells: &HashMap<RoleName, CellId>,
+) -> Result<AppBundleSource> {
+    if existing_cells.is_empty() {
+        return Ok(AppBundleSource::Path(bundle.path.clone()));
+    }
+
+    let bundle = Bundle::<AppManifest>::read_from_file(&bundle.path).await?;
+    let AppManifest::V1(mut manifest) = bundle.manifest().clone();
+    for role in manifest.roles.iter_mut() {
+        if existing_cells.contains_key(&role.name) {
+            role.provisioning = Some(CellProvisioning::UseExisting { protected: false });
+        }
+    }
+    Ok(AppBundleSource::Bundle(
+        bundle.update_manifest(AppManifest::V1(manifest))?.into(),
+    ))
+}
+
+/// Uninstalls what is left of a failed step, if anything
+async fn remove_leftover(ws: &mut Ws, installed_app_id: &str) {
+    if let Err(e) = handle_holochain_uninstall(&mut ws.admin, &installed_app_id.to_string()).await {
+        debug!("nothing to clean up for {}: {}", installed_app_id, e);
+    }
+    ws.apps.remove(installed_app_id);
+}
+
+async fn is_installed(ws: &mut Ws, installed_app_id: &str) -> bool {
+    match ws.admin.list_apps(None).await {
+        Ok(apps) => apps
+            .iter()
+            .any(|info| info.installed_app_id == installed_app_id),
+        Err(_) => false,
+    }
+}
+
+/// Returns cells of running roles whose DNA is the same in the new version, by role name
+fn select_kept_cells(
+    running: &HashMap<RoleName, CellId>,
+    new_roles: &[(RoleName, Vec<String>)],
+) -> HashMap<RoleName, CellId> {
+    running
+        .iter()
+        .filter(|(role_name, cell_id)| {
+            let dna_hash = DnaHashB64::from(cell_id.dna_hash().clone()).to_string();
+            new_roles
+                .iter()
+                .any(|(name, hashes)| name == *role_name && hashes.contains(&dna_hash))
+        })
+        .map(|(role_name, cell_id)| (role_name.clone(), cell_id.clone()))
+        .collect()
+}
+
+/// Returns cells of provisioned roles of installed app
+fn get_role_cells(info: &AppInfo) -> HashMap<RoleName, CellId> {
+    info.cell_info
+        .iter()
+        .filter_map(|(role_name, cells)| {
+            cells.iter().find_map(|cell| match cell {
+                CellInfo::Provisioned(cell) => Some((role_name.clone(), cell.cell_id.clone())),
+                _ => None,
+            })
+        })
+        .collect()
+}
+
+/// Returns hashes of DNAs of provisioned cells of installed app, ordered by role name
+fn get_role_dnas(info: &AppInfo) -> Vec<RoleDna> {
+    let mut dnas: Vec<RoleDna> = info
+        .cell_info
+        .iter()
+        .flat_map(|(role_name, cells)| {
+            cells.iter().filter_map(move |cell| match cell {
+                CellInfo::Provisioned(cell) => Some(RoleDna {
+                    role_name: role_name.clone(),
+                    dna_hash: DnaHashB64::from(cell.cell_id.dna_hash().clone()).to_string(),
+                }),
+                _ => None,
+            })
+        })
+        .collect();
+    dnas.sort_by(|a, b| a.role_name.cmp(&b.role_name));
+    dnas
+}
+
+/// Happ is outdated when the set of installed DNA hashes differs from the published one.
+/// Happs that publish no DNA hashes can not be checked and are never reported as outdated.
+fn is_outdated(installed: &[RoleDna], published: &[DnaResource]) -> bool {
+    if published.is_empty() {
+        return false;
+    }
+
+    let installed: BTreeSet<&str> = installed.iter().map(|dna| dna.dna_hash.as_str()).collect();
+    let published: BTreeSet<&str> = published.iter().map(|dna| dna.hash.as_str()).collect();
+    installed != published
+}
+
+fn list_agent_instances(installed_apps: &[AppInfo], id: &ActionHashB64) -> Vec<String> {
+    installed_apps
+        .iter()
+        .map(|info| info.installed_app_id.clone())
+        .filter(|installed_app_id| installed_app_id.starts_with(&format!("{}:uhCA", id)))
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct RoleDna {
+    pub role_name: String,
+    pub dna_hash: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct OutdatedHapp {
+    pub happ_id: ActionHashB64,
+    pub name: String,
+    pub bundle_url: String,
+    pub last_edited: Timestamp,
+    pub installed_dnas: Vec<RoleDna>,
+    pub published_dnas: Vec<RoleDna>,
+    pub agent_instances: usize,
+}
+
+impl OutdatedHapp {
+    fn new(
+        happ: &PresentedHappBundle,
+        installed_dnas: Vec<RoleDna>,
+        installed_apps: &HashMap<String, AppInfo>,
+    ) -> Self {
+        let agent_prefix = format!("{}:uhCA", happ.id);
+        OutdatedHapp {
+            happ_id: happ.id.clone(),
+            name: happ.name.clone(),
+            bundle_url: happ.bundle_url.clone(),
+            last_edited: happ.last_edited,
+            installed_dnas,
+            published_dnas: happ
+                .dnas
+                .iter()
+                .map(|dna| RoleDna {
+                    role_name: dna.nick.clone(),
+                    dna_hash: dna.hash.clone(),
+                })
+                .collect(),
+            agent_instances: installed_apps
+                .keys()
+                .filter(|installed_app_id| installed_app_id.starts_with(&agent_prefix))
+                .count(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct UpgradeReport {
+    pub happ_id: ActionHashB64,
+    pub upgraded: bool,          // false when happ was already up to date
+    pub kept_roles: Vec<String>, // roles whose DNA has not changed, their source chains are kept
+    pub previous_dnas: Vec<RoleDna>,
+    pub current_dnas: Vec<RoleDna>,
+    pub agent_instances: Vec<String>, // left running on previous DNAs
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use holochain_types::dna::{AgentPubKey, DnaHash, DnaHashB64};
+    use holochain_types::prelude::CellId;
+
+    use super::{is_outdated, select_kept_cells, RoleDna};
+    use crate::common::types::DnaResource;
+
+    fn installed(hashes: &[&str]) -> Vec<RoleDna> {
+        hashes
+            .iter()
+            .enumerate()
+            .map(|(i, hash)| RoleDna {
+                role_name: format!("role_{}", i),
+                dna_hash: hash.to_string(),
+            })
+            .collect()
+    }
+
+    fn published(hashes: &[&str]) -> Vec<DnaResource> {
+        hashes
+            .iter()
+            .map(|hash| DnaResource {
+                hash: hash.to_string(),
+                src_url: "https://example.com/test.dna".to_string(),
+                nick: "test".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detects_outdated_dnas() {
+        assert!(!is_outdated(
+            &installed(&["uhC0kA", "uhC0kB"]),
+            &published(&["uhC0kB", "uhC0kA"])
+        ));
+        assert!(is_outdated(
+            &installed(&["uhC0kA", "uhC0kB"]),
+            &published(&["uhC0kA", "uhC0kC"])
+        ));
+        assert!(is_outdated(
+            &installed(&["uhC0kA"]),
+            &published(&["uhC0kA", "uhC0kB"])
+        ));
+        // nothing to compare with
+        assert!(!is_outdated(&installed(&["uhC0kA"]), &published(&[])));
+    }
+
+    #[test]
+    fn keeps_cells_of_unchanged_roles() {
+        let agent = AgentPubKey::from_raw_36(vec![0; 36]);
+        let cell = |n: u8| CellId::new(DnaHash::from_raw_36(vec![n; 36]), agent.clone());
+        let hash = |n: u8| DnaHashB64::from(DnaHash::from_raw_36(vec![n; 36])).to_string();
+
+        let running = HashMap::from([
+            ("main".to_string(), cell(1)),
+            ("chat".to_string(), cell(2)),
+            ("files".to_string(), cell(3)),
+        ]);
+        let new_roles = vec![
+            // DNA matches with network seed applied
+            ("main".to_string(), vec![hash(9), hash(1)]),
+            ("chat".to_string(), vec![hash(4), hash(4)]),
+            // same DNA under another role is a new cell
+            ("media".to_string(), vec![hash(3), hash(3)]),
+        ];
+
+        let kept = select_kept_cells(&running, &new_roles);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept.get("main"), Some(&cell(1)));
+    }
+}
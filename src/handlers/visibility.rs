@@ -0,0 +1,147 @@
+// Rules deciding which hosted happs are shown to host. Hidden happs are left out of `/apps/hosted`,
+// `/holoport/usage` and host's earnings, pinned happs are listed first in `/apps/hosted`.
+// Rules are stored in `<HPOS_API_DATA_DIR>/visibility.json`. Until host saves own rules
+// happs with a `special_installed_app_id` (e.g. Cloud Console, which runs in core app) are hidden.
+
+use std::collections::HashSet;
+
+use anyhow::Result;
+use holochain_types::dna::{ActionHashB64, AgentPubKeyB64};
+use rocket::serde::{Deserialize, Serialize};
+
+use super::hosted_happs::get_all_happs;
+use crate::common::store::{load_json, save_json};
+use crate::common::types::PresentedHappBundle;
+use crate::hpos::{get_data_dir, Ws};
+
+pub fn handle_get_rules() -> Result<VisibilityRules> {
+    load_json(&get_data_dir().join("visibility.json"))
+}
+
+/// Replaces all visibility rules with `rules`
+pub fn handle_set_rules(rules: VisibilityRules) -> Result<VisibilityRules> {
+    save_json(&get_data_dir().join("visibility.json"), &rules)?;
+    Ok(rules)
+}
+
+/// Returns ids of all happs in hha that are hidden by the rules
+pub async fn get_hidden_happ_ids(ws: &mut Ws) -> Result<HashSet<ActionHashB64>> {
+    let rules = handle_get_rules()?;
+    Ok(get_all_happs(ws)
+        .await?
+        .into_iter()
+        .filter(|happ| rules.is_hidden(happ))
+        .map(|happ| happ.id)
+        .collect())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct VisibilityRules {
+    pub rules: Vec<VisibilityRule>,
+}
+
+impl Default for VisibilityRules {
+    fn default() -> Self {
+        VisibilityRules {
+            rules: vec![VisibilityRule {
+                action: RuleAction::Hide,
+                matcher: HappMatcher::SpecialInstalledAppId(None),
+            }],
+        }
+    }
+}
+
+impl VisibilityRules {
+    pub fn is_hidden(&self, happ: &PresentedHappBundle) -> bool {
+        self.any_matches(RuleAction::Hide, happ)
+    }
+
+    /// Hide rules take precedence, so a happ that is hidden is never pinned
+    pub fn is_pinned(&self, happ: &PresentedHappBundle) -> bool {
+        !self.is_hidden(happ) && self.any_matches(RuleAction::Pin, happ)
+    }
+
+    fn any_matches(&self, action: RuleAction, happ: &PresentedHappBundle) -> bool {
+        self.rules.iter().any(|rule| {
+            rule.action == action
+                && rule.matcher.matches(
+                    &happ.id,
+                    &happ.provider_pubkey,
+                    happ.special_installed_app_id.as_deref(),
+                )
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct VisibilityRule {
+    pub action: RuleAction,
+    pub matcher: HappMatcher,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub enum RuleAction {
+    Hide,
+    Pin,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(tag = "by", content = "value", rename_all = "camelCase")]
+pub enum HappMatcher {
+    HappId(ActionHashB64),
+    Publisher(AgentPubKeyB64),
+    SpecialInstalledAppId(Option<String>), // None matches every happ that has a special_installed_app_id
+}
+
+impl HappMatcher {
+    fn matches(
+        &self,
+        happ_id: &ActionHashB64,
+        publisher: &AgentPubKeyB64,
+        special_installed_app_id: Option<&str>,
+    ) -> bool {
+        match self {
+            HappMatcher::HappId(id) => id == happ_id,
+            HappMatcher::Publisher(pubkey) => pubkey == publisher,
+            HappMatcher::SpecialInstalledAppId(None) => special_installed_app_id.is_some(),
+            HappMatcher::SpecialInstalledAppId(Some(app_id)) => {
+                special_installed_app_id == Some(app_id.as_str())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use holochain_types::dna::{ActionHashB64, AgentPubKeyB64};
+
+    use super::HappMatcher;
+
+    #[test]
+    fn matches_happs_by_id_publisher_and_special_app_id() {
+        let happ_id =
+            ActionHashB64::from_b64_str("uhCkklkJVx4u17eCaaKg_phRJsHOj9u57v_4cHQR-Bd9tb-vePRyC")
+                .unwrap();
+        let publisher =
+            AgentPubKeyB64::from_b64_str("uhCAk76ikqpgxdisc5bRJcCY-lOTVB8osHEkiGj8hP4kxA01jSrjC")
+                .unwrap();
+
+        assert!(HappMatcher::HappId(happ_id.clone()).matches(&happ_id, &publisher, None));
+        assert!(HappMatcher::Publisher(publisher.clone()).matches(&happ_id, &publisher, None));
+
+        let any_special = HappMatcher::SpecialInstalledAppId(None);
+        assert!(any_special.matches(&happ_id, &publisher, Some("core-app")));
+        assert!(!any_special.matches(&happ_id, &publisher, None));
+
+        let core_app = HappMatcher::SpecialInstalledAppId(Some("core-app".to_string()));
+        assert!(core_app.matches(&happ_id, &publisher, Some("core-app")));
+        assert!(!core_app.matches(&happ_id, &publisher, Some("other-app")));
+    }
+}
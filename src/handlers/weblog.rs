@@ -0,0 +1,237 @@
+// Conversion of servicelogger's activity logs into formats understood by web server log analysers (e.g. GoAccess).
+// A zome call is represented as a `POST /<role_name>/<zome>/<function>` request made by web user's agent.
+
+use anyhow::Result;
+use holochain_types::dna::ActionHashB64;
+use rocket::{
+    http::ContentType,
+    serde::{
+        json::{serde_json, Value},
+        Deserialize, Serialize,
+    },
+    FromFormField,
+};
+
+use super::hosted_happs::{
+    handle_get_service_logs, ActivityLog, LogEntry, LogEntryType, LogFilter, LogRecord,
+};
+use crate::common::series::timestamp_to_date_time;
+use crate::hpos::Ws;
+
+#[derive(Serialize, Deserialize, FromFormField, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub enum ExportFormat {
+    Clf,      // Common Log Format
+    Combined, // Combined Log Format
+    Ndjson,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> ContentType {
+        match self {
+            ExportFormat::Clf | ExportFormat::Combined => ContentType::Plain,
+            ExportFormat::Ndjson => ContentType::new("application", "x-ndjson"),
+            ExportFormat::Csv => ContentType::CSV,
+        }
+    }
+}
+
+pub const CSV_HEADER: &str =
+    "timestamp,source_ip,agent,role_name,zome,function,status_code,bandwidth,cpu";
+
+/// Returns records of activity logs of happ `id` matching `filter`, oldest first.
+/// Records are formatted with `to_weblog_line` by the caller, so that lock on `ws` does not have to be held for it.
+pub async fn handle_export_service_logs(
+    ws: &mut Ws,
+    id: ActionHashB64,
+    mut filter: LogFilter,
+) -> Result<Vec<LogRecord>> {
+    filter.entry_type = Some(LogEntryType::ActivityLog);
+
+    let page = handle_get_service_logs(ws, id, filter, None, usize::MAX).await?;
+
+    Ok(page.entries.into_iter().rev().collect())
+}
+
+/// Formats activity log from `record` as a single line in `format` terminated with a newline.
+/// Records of other entries are skipped.
+pub fn to_weblog_line(record: &LogRecord, format: ExportFormat) -> Option<String> {
+    let log: &ActivityLog = match &record.entry {
+        LogEntry::ActivityLog(log) => log,
+        LogEntry::DiskUsageLog(_) => return None,
+    };
+
+    let request = &log.request.request;
+    let call_spec = &request.call_spec;
+    let weblog = &log.response.weblog_compat;
+    let metrics = &log.response.host_metrics;
+    let time = timestamp_to_date_time(request.timestamp);
+    let agent = log.request.agent_id.to_string();
+
+    let line = match format {
+        ExportFormat::Clf | ExportFormat::Combined => {
+            let mut line = format!(
+                "{} - {} [{}] \"POST /{}/{}/{} HTTP/1.1\" {} {}",
+                weblog.source_ip,
+                agent,
+                time.format("%d/%b/%Y:%H:%M:%S %z"),
+                escape_path_segment(&call_spec.role_name),
+                escape_path_segment(&call_spec.zome),
+                escape_path_segment(&call_spec.function),
+                weblog.status_code,
+                metrics.bandwidth
+            );
+            if format == ExportFormat::Combined {
+                // neither referer nor user agent are recorded by servicelogger
+                line.push_str(" \"-\" \"-\"");
+            }
+            line
+        }
+        ExportFormat::Ndjson => {
+            let entry: Value = serde_json::json!({
+                "timestamp": time.to_rfc3339(),
+                "sourceIp": weblog.source_ip,
+                "agent": agent,
+                "roleName": call_spec.role_name,
+                "zome": call_spec.zome,
+                "function": call_spec.function,
+                "statusCode": weblog.status_code,
+                "bandwidth": metrics.bandwidth,
+                "cpu": metrics.cpu,
+            });
+            entry.to_string()
+        }
+        ExportFormat::Csv => [
+            time.to_rfc3339(),
+            weblog.source_ip.clone(),
+            agent,
+            call_spec.role_name.clone(),
+            call_spec.zome.clone(),
+            call_spec.function.clone(),
+            weblog.status_code.to_string(),
+            metrics.bandwidth.to_string(),
+            metrics.cpu.to_string(),
+        ]
+        .iter()
+        .map(|field| escape_csv(field))
+        .collect::<Vec<String>>()
+        .join(","),
+    };
+
+    Some(format!("{}\n", line))
+}
+
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Percent-encodes `segment` of request path, so that quotes and spaces in names of zomes
+/// and functions do not break the quoted request line of CLF
+fn escape_path_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use holochain_client::AgentPubKey;
+    use holochain_types::{
+        dna::ActionHash,
+        prelude::{Signature, Timestamp},
+    };
+
+    use super::{escape_csv, escape_path_segment, to_weblog_line, ExportFormat};
+    use crate::handlers::hosted_happs::*;
+
+    fn record() -> LogRecord {
+        let hash = ActionHash::from_raw_36(vec![0; 36]);
+        let timestamp = Timestamp::from_micros(1719348253000000); // 2024-06-25T20:44:13Z
+        LogRecord {
+            action_seq: 5,
+            timestamp,
+            entry: LogEntry::ActivityLog(Box::new(ActivityLog {
+                request: ClientRequest {
+                    agent_id: AgentPubKey::from_raw_36(vec![1; 36]),
+                    request: RequestPayload {
+                        host_id: "host".to_string(),
+                        timestamp,
+                        hha_pricing_pref: hash.clone(),
+                        call_spec: CallSpec {
+                            args_hash: vec![],
+                            function: "get_posts".to_string(),
+                            zome: "posts".to_string(),
+                            role_name: "main".to_string(),
+                            hha_hash: hash,
+                        },
+                    },
+                    request_signature: Signature([0; 64]),
+                },
+                response: HostResponse {
+                    host_metrics: HostMetrics {
+                        cpu: 7,
+                        bandwidth: 512,
+                    },
+                    weblog_compat: ExtraWebLogData {
+                        source_ip: "10.0.0.1".to_string(),
+                        status_code: 200,
+                    },
+                },
+            })),
+        }
+    }
+
+    #[test]
+    fn formats_activity_log_as_weblog_lines() {
+        let record = record();
+        let agent = AgentPubKey::from_raw_36(vec![1; 36]).to_string();
+
+        assert_eq!(
+            to_weblog_line(&record, ExportFormat::Clf).unwrap(),
+            format!(
+                "10.0.0.1 - {} [25/Jun/2024:20:44:13 +0000] \"POST /main/posts/get_posts HTTP/1.1\" 200 512\n",
+                agent
+            )
+        );
+        assert!(to_weblog_line(&record, ExportFormat::Combined)
+            .unwrap()
+            .ends_with(" 200 512 \"-\" \"-\"\n"));
+        assert_eq!(
+            to_weblog_line(&record, ExportFormat::Csv).unwrap(),
+            format!(
+                "2024-06-25T20:44:13+00:00,10.0.0.1,{},main,posts,get_posts,200,512,7\n",
+                agent
+            )
+        );
+        assert!(to_weblog_line(&record, ExportFormat::Ndjson)
+            .unwrap()
+            .contains("\"statusCode\":200"));
+    }
+
+    #[test]
+    fn escapes_csv_fields() {
+        assert_eq!(escape_csv("plain"), "plain");
+        assert_eq!(escape_csv("a,b"), "\"a,b\"");
+        assert_eq!(escape_csv("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn escapes_request_path_segments() {
+        assert_eq!(escape_path_segment("get_posts"), "get_posts");
+        assert_eq!(escape_path_segment("get posts"), "get%20posts");
+        assert_eq!(escape_path_segment("a\"b"), "a%22b");
+        assert_eq!(escape_path_segment("zöme"), "z%C3%B6me");
+    }
+}
@@ -10,11 +10,16 @@ use hpos_hc_connect::{
 };
 use rocket::tokio::sync::Mutex;
 use std::process::{Command, Stdio};
-use std::{env, path::PathBuf};
+use std::sync::Arc;
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
 
 /// Mutex that guards access to admin websocket and lair keystore connection. This Mutex also stores
 /// information about app interfaces enabled in holochain that websocket zome calls to specific apps can open.
-pub type WsMutex = Mutex<Ws>;
+/// It is shared with tasks running in the background, such as install jobs.
+pub type WsMutex = Arc<Mutex<Ws>>;
 
 /// Connects to Holochain using env vars that are specific for a flavour of a network (devNet, mainNet, etc)
 /// Env vars required:
@@ -25,6 +30,7 @@ pub type WsMutex = Mutex<Ws>;
 /// - SL_COLLECTOR_PUB_KEY
 /// - HOST_PUBKEY_PATH (only required in non-test envs)
 /// - IS_TEST_ENV (only required in a test env)
+/// - HPOS_API_DATA_DIR (optional)
 
 /// Opens a single admin websocket connection to holochain using pre-initiated keystore
 pub struct Ws {
@@ -38,7 +44,7 @@ pub struct Ws {
 }
 
 impl Ws {
-    pub async fn connect() -> Result<Mutex<Self>> {
+    pub async fn connect() -> Result<WsMutex> {
         let admin = AdminWebsocket::connect(ADMIN_PORT)
             .await
             .context("failed to connect to holochain's app interface")?;
@@ -72,7 +78,7 @@ impl Ws {
 
         let apps = HashMap::new();
 
-        Ok(Mutex::new(Self {
+        Ok(Arc::new(Mutex::new(Self {
             admin,
             keystore,
             apps,
@@ -80,7 +86,7 @@ impl Ws {
             base_sl,
             hp_id,
             host_pub_key,
-        }))
+        })))
     }
 
     async fn open_connection(&mut self, app_id: String) -> Result<AppConnection> {
@@ -121,6 +127,34 @@ pub fn get_host_pubkey() -> Result<Option<AgentPubKey>> {
     Ok(Some(host_pub_key))
 }
 
+/// Directory where the API keeps files it owns (e.g. downloaded happ bundles)
+/// Read from HPOS_API_DATA_DIR, falls back to a directory in the system's temp dir
+pub fn get_data_dir() -> PathBuf {
+    match env::var("HPOS_API_DATA_DIR") {
+        Ok(p) => p.into(),
+        Err(_) => env::temp_dir().join("hpos-api-rust"),
+    }
+}
+
+/// Returns number of bytes available to the API on file system holding `path`, as reported by `df`
+pub fn get_available_disk_space(path: &Path) -> Result<u64> {
+    let output = Command::new("df")
+        .arg("-Pk")
+        .arg(path)
+        .output()
+        .context("Failed to execute `df` command")?;
+
+    parse_df_available(&String::from_utf8_lossy(&output.stdout))
+        .ok_or(anyhow!("Failed to parse output of `df` for {:?}", path))
+}
+
+/// Parses available space out of POSIX output of `df -Pk`, which is given in 1024-byte blocks
+fn parse_df_available(output: &str) -> Option<u64> {
+    let line = output.lines().nth(1)?;
+    let available_kb: u64 = line.split_whitespace().nth(3)?.parse().ok()?;
+    Some(available_kb * 1024)
+}
+
 pub fn get_holoport_id() -> String {
     if std::env::var("IS_TEST_ENV").is_ok() {
         return "W3cPOJ9Em4vR3A4jlLwD7n++wqk3rNP3Rk59UHxjPI7rAZ8HKmlQQdFHuUB5XfnSw2eMgV+JbiK7fV5VEYaSGQ==".to_string();
@@ -154,7 +188,7 @@ pub fn get_holoport_id() -> String {
 mod test {
     use std::env::{self, set_var};
 
-    use super::get_host_pubkey;
+    use super::{get_host_pubkey, parse_df_available};
 
     #[test]
     fn parse_pubkey_from_file() {
@@ -168,4 +202,11 @@ mod test {
             format!("{:?}", host_key)
         );
     }
+
+    #[test]
+    fn parse_available_disk_space() {
+        let output = "Filesystem     1024-blocks     Used Available Capacity Mounted on\n/dev/sda1        102400000 51200000  51200000      50% /\n";
+        assert_eq!(parse_df_available(output), Some(51200000 * 1024));
+        assert_eq!(parse_df_available(""), None);
+    }
 }
@@ -4,13 +4,26 @@ mod hpos;
 pub mod routes;
 
 use common::hbs::HBS;
+use handlers::auto_accept::spawn_auto_accept;
+use handlers::install::InstallJobs;
+use handlers::quota::spawn_quota_checker;
+use handlers::reconcile::{spawn_reconciler, Reconciler};
 use hpos::Ws;
 use log::debug;
-use rocket::{self, routes, Build, Rocket};
+use rocket::{
+    self,
+    data::{Limits, ToByteUnit},
+    routes, Build, Rocket,
+};
 
+use routes::apps::bundles::*;
 use routes::apps::call_zome::*;
 use routes::apps::core::*;
 use routes::apps::hosted::*;
+use routes::apps::installed::*;
+use routes::apps::published::*;
+use routes::apps::reconcile::*;
+use routes::apps::self_hosted::*;
 use routes::holoport::usage::*;
 use routes::host::billing_preferences::*;
 use routes::host::earnings::*;
@@ -20,6 +33,9 @@ use routes::host::redeemable_histogram::*;
 use routes::host::redemptions::*;
 use routes::index;
 
+/// Max size of a happ bundle uploaded to `/apps/self_hosted`, in MiB
+const MAX_UPLOAD_SIZE: u64 = 256;
+
 pub async fn rocket() -> Rocket<Build> {
     if let Err(e) = env_logger::try_init() {
         debug!(
@@ -34,9 +50,28 @@ pub async fn rocket() -> Rocket<Build> {
 
     let hbs = HBS::new();
 
-    rocket::build()
+    let install_jobs = InstallJobs::new();
+
+    let reconciler = Reconciler::new();
+    spawn_reconciler(ws.clone(), reconciler.clone());
+
+    spawn_quota_checker(ws.clone());
+
+    spawn_auto_accept(ws.clone(), install_jobs.clone());
+
+    // happ bundles uploaded for self-hosting are bigger than default limits
+    let figment = rocket::Config::figment().merge((
+        "limits",
+        Limits::new()
+            .limit("file", MAX_UPLOAD_SIZE.mebibytes())
+            .limit("data-form", MAX_UPLOAD_SIZE.mebibytes()),
+    ));
+
+    rocket::custom(figment)
         .manage(ws)
         .manage(hbs)
+        .manage(install_jobs)
+        .manage(reconciler)
         .mount(
             "/",
             routes![
@@ -46,15 +81,59 @@ pub async fn rocket() -> Rocket<Build> {
         .mount(
             "/apps",
             routes![
-                get_all,      // done
-                get_by_id,    // done
-                enable,       // done
-                disable,      // done
-                call_zome,    // done
-                logs,         // done
-                version,      // done
-                install_app,  // done
-                register_app  // done
+                get_all,   // done
+                get_by_id, // done
+                enable,    // done
+                disable,   // done
+                bulk,
+                uninstall,
+                hosted_agents,
+                pause_agent,
+                uninstall_agent,
+                happ_storage,
+                preflight,
+                get_quotas,
+                set_quotas,
+                check_quotas,
+                get_visibility,
+                set_visibility,
+                get_auto_accept,
+                set_auto_accept,
+                run_auto_accept,
+                auto_accept_log,
+                happ_hosting_history,
+                outdated_happs,
+                upgrade_app,
+                get_preferences,
+                happ_earnings,
+                usage_series,
+                set_preferences,
+                call_zome, // done
+                logs,      // done
+                export_logs,
+                version,     // done
+                install_app, // done
+                install_jobs,
+                install_job,
+                cancel_install_job,
+                register_app, // done
+                update_app,
+                get_bundles,
+                purge_bundles,
+                install_self_hosted,
+                get_self_hosted,
+                enable_self_hosted,
+                disable_self_hosted,
+                remove_self_hosted,
+                get_published,
+                create_draft,
+                publish_draft,
+                pause_published,
+                unpause_published,
+                reconcile_report,
+                reconcile_repair,
+                reconcile_log,
+                installed_apps,
             ],
         )
         .mount(
@@ -0,0 +1,24 @@
+use crate::handlers::bundles::{self, BundleCacheReport, CachedBundle};
+use rocket::{
+    http::Status,
+    serde::json::Json,
+    {delete, get},
+};
+
+/// Lists bundles kept in local bundle cache, most recently used first
+#[get("/bundles")]
+pub async fn get_bundles() -> Result<Json<BundleCacheReport>, (Status, String)> {
+    Ok(Json(bundles::handle_get_cache().map_err(|e| {
+        (Status::InternalServerError, e.to_string())
+    })?))
+}
+
+/// Removes bundle with sha256 `hash` from local bundle cache, or all bundles if `hash` is omitted
+#[delete("/bundles?<hash>")]
+pub async fn purge_bundles(
+    hash: Option<&str>,
+) -> Result<Json<Vec<CachedBundle>>, (Status, String)> {
+    Ok(Json(
+        bundles::handle_purge_cache(hash).map_err(|e| (Status::BadRequest, e.to_string()))?,
+    ))
+}
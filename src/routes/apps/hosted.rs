@@ -1,23 +1,35 @@
 use crate::{
-    common::types::{HappAndHost, HappInput, PresentedHappBundle, Transaction},
-    handlers::{hosted_happs::*, install, register},
+    common::{
+        fuel,
+        hbs::HbSMutex,
+        series::{parse_range, Bucket, Buckets},
+        types::{HappAndHost, HappInput, PresentedHappBundle, Transaction},
+    },
+    handlers::{
+        agents, auto_accept,
+        hosted_happs::*,
+        hosting_history,
+        install::{self, InstallJob, InstallJobsMutex, JobProgress},
+        preferences, preflight, quota, register, storage, uninstall, upgrade, visibility,
+        weblog::{handle_export_service_logs, to_weblog_line, ExportFormat, CSV_HEADER},
+    },
     hpos::{Ws, WsMutex},
 };
 use anyhow::{anyhow, Result};
-use holochain_client::AgentPubKey;
+use chrono::{Days, Utc};
 use holochain_types::{
-    dna::ActionHashB64,
+    dna::{ActionHashB64, AgentPubKeyB64},
     prelude::{holochain_serial, SerializedBytes, Timestamp},
 };
 use holofuel_types::fuel::Fuel;
-use hpos_hc_connect::app_connection::CoreAppRoleName;
 use log::warn;
 use rocket::{
-    http::Status,
+    http::{ContentType, Status},
+    response::stream::TextStream,
     serde::{json::Json, Deserialize, Serialize},
-    {get, post, State},
+    {delete, get, post, put, State},
 };
-use std::{fmt, str::FromStr, time::Duration};
+use std::{str::FromStr, time::Duration};
 
 #[get("/hosted?<usage_interval>&<quantity>")]
 pub async fn get_all(
@@ -30,11 +42,7 @@ pub async fn get_all(
     Ok(Json(
         handle_get_all(usage_interval, quantity, &mut ws)
             .await
-            .map_err(|e| (Status::InternalServerError, e.to_string()))?
-            // filter out cloud console hApp for host console ui
-            .into_iter()
-            .filter(|happ| happ.name != "Cloud Console")
-            .collect(),
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?,
     ))
 }
 
@@ -60,8 +68,11 @@ pub async fn get_by_id(
 #[post("/hosted/<id>/enable")]
 pub async fn enable(id: &str, wsm: &State<WsMutex>) -> Result<(), (Status, String)> {
     let mut ws = wsm.lock().await;
+    let payload = HappAndHost::init(id)
+        .await
+        .map_err(|e| (Status::BadRequest, e.to_string()))?;
 
-    handle_enable(&mut ws, id)
+    handle_enable(&mut ws, payload)
         .await
         .map_err(|e| (Status::InternalServerError, e.to_string()))
 }
@@ -78,47 +89,460 @@ pub async fn disable(id: &str, wsm: &State<WsMutex>) -> Result<(), (Status, Stri
         .map_err(|e| (Status::InternalServerError, e.to_string()))
 }
 
-#[get("/hosted/<id>/logs?<days>")]
-pub async fn logs(
+/// Enables or disables a list of happs and/or all happs matching a filter in one go
+/// -- returns result of the action for each happ
+#[post("/hosted/bulk", format = "json", data = "<body>")]
+pub async fn bulk(
+    body: Json<BulkHostingBody>,
+    wsm: &State<WsMutex>,
+) -> Result<Json<Vec<BulkHostingResult>>, (Status, String)> {
+    if body.happ_ids.is_empty() && body.filter.is_none() {
+        return Err((
+            Status::BadRequest,
+            "Either happIds or filter has to be provided".to_string(),
+        ));
+    }
+
+    let mut ws = wsm.lock().await;
+
+    Ok(Json(
+        handle_bulk_hosting(&mut ws, body.into_inner())
+            .await
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?,
+    ))
+}
+
+/// Disables hosted happ in hha and removes all of its instances and bundles from holoport
+/// -- with `keep_data=true` nothing is removed and response only lists what would be
+#[delete("/hosted/<id>?<keep_data>")]
+pub async fn uninstall(
     id: &str,
-    days: Option<i32>,
+    keep_data: Option<bool>,
     wsm: &State<WsMutex>,
-) -> Result<Json<Vec<LogEntry>>, (Status, String)> {
+) -> Result<Json<uninstall::UninstallReport>, (Status, String)> {
     let mut ws = wsm.lock().await;
 
     let id = ActionHashB64::from_b64_str(id).map_err(|e| (Status::BadRequest, e.to_string()))?;
-    let days = days.unwrap_or(7); // 7 days
 
     Ok(Json(
-        handle_get_service_logs(&mut ws, id, days)
+        uninstall::handle_uninstall_app(&mut ws, id, keep_data.unwrap_or(false))
             .await
             .map_err(|e| (Status::InternalServerError, e.to_string()))?,
     ))
 }
 
-#[post("/hosted/install", format = "application/json", data = "<payload>")]
-pub async fn install_app(
+/// Lists web agents hosted for a happ, one instance of the happ per agent
+#[get("/hosted/<id>/agents")]
+pub async fn hosted_agents(
+    id: &str,
     wsm: &State<WsMutex>,
-    payload: install::InstallHappBody,
-) -> Result<String, (Status, String)> {
+) -> Result<Json<Vec<agents::HostedAgent>>, (Status, String)> {
     let mut ws = wsm.lock().await;
-    install::handle_install_app(&mut ws, payload)
+
+    let id = ActionHashB64::from_b64_str(id).map_err(|e| (Status::BadRequest, e.to_string()))?;
+
+    Ok(Json(
+        agents::handle_get_hosted_agents(&mut ws, id)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?,
+    ))
+}
+
+/// Disables happ's instance of a single web agent, its source chain is kept
+#[post("/hosted/<id>/agents/<agent>/pause")]
+pub async fn pause_agent(
+    id: &str,
+    agent: &str,
+    wsm: &State<WsMutex>,
+) -> Result<(), (Status, String)> {
+    let mut ws = wsm.lock().await;
+
+    let id = ActionHashB64::from_b64_str(id).map_err(|e| (Status::BadRequest, e.to_string()))?;
+    let agent =
+        AgentPubKeyB64::from_b64_str(agent).map_err(|e| (Status::BadRequest, e.to_string()))?;
+
+    match agents::handle_pause_agent(&mut ws, id.clone(), agent.clone())
+        .await
+        .map_err(|e| (Status::InternalServerError, e.to_string()))?
+    {
+        Some(_) => Ok(()),
+        None => Err((
+            Status::NotFound,
+            format!(
+                "Agent {} is not hosted for happ {} on this holoport",
+                agent, id
+            ),
+        )),
+    }
+}
+
+/// Uninstalls happ's instance of a single web agent together with its source chain
+#[delete("/hosted/<id>/agents/<agent>")]
+pub async fn uninstall_agent(
+    id: &str,
+    agent: &str,
+    wsm: &State<WsMutex>,
+) -> Result<(), (Status, String)> {
+    let mut ws = wsm.lock().await;
+
+    let id = ActionHashB64::from_b64_str(id).map_err(|e| (Status::BadRequest, e.to_string()))?;
+    let agent =
+        AgentPubKeyB64::from_b64_str(agent).map_err(|e| (Status::BadRequest, e.to_string()))?;
+
+    match agents::handle_uninstall_agent(&mut ws, id.clone(), agent.clone())
+        .await
+        .map_err(|e| (Status::InternalServerError, e.to_string()))?
+    {
+        Some(_) => Ok(()),
+        None => Err((
+            Status::NotFound,
+            format!(
+                "Agent {} is not hosted for happ {} on this holoport",
+                agent, id
+            ),
+        )),
+    }
+}
+
+/// Lists installed happs whose DNAs differ from DNAs published in hha
+#[get("/hosted/outdated")]
+pub async fn outdated_happs(
+    wsm: &State<WsMutex>,
+) -> Result<Json<Vec<upgrade::OutdatedHapp>>, (Status, String)> {
+    let mut ws = wsm.lock().await;
+
+    Ok(Json(upgrade::handle_get_outdated(&mut ws).await.map_err(
+        |e| (Status::InternalServerError, e.to_string()),
+    )?))
+}
+
+/// Reinstalls host's instance of a happ from the bundle currently published in hha
+/// -- does nothing when the happ is up to date
+#[post("/hosted/<id>/upgrade")]
+pub async fn upgrade_app(
+    id: &str,
+    wsm: &State<WsMutex>,
+) -> Result<Json<upgrade::UpgradeReport>, (Status, String)> {
+    let mut ws = wsm.lock().await;
+
+    let id = ActionHashB64::from_b64_str(id).map_err(|e| (Status::BadRequest, e.to_string()))?;
+
+    Ok(Json(
+        upgrade::handle_upgrade_app(&mut ws, id)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?,
+    ))
+}
+
+/// Checks whether a happ fits this holoport before hosting it:
+/// jurisdiction, pricing, categories, disk space and servicelogger provisioning
+#[get("/hosted/<id>/preflight")]
+pub async fn preflight(
+    id: &str,
+    wsm: &State<WsMutex>,
+    hbsm: &State<HbSMutex>,
+) -> Result<Json<preflight::PreflightReport>, (Status, String)> {
+    let mut ws = wsm.lock().await;
+    let mut hbs = hbsm.lock().await;
+
+    let id = ActionHashB64::from_b64_str(id).map_err(|e| (Status::BadRequest, e.to_string()))?;
+
+    Ok(Json(
+        preflight::handle_preflight(&mut ws, &mut hbs, id)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?,
+    ))
+}
+
+/// Returns the latest disk usage of a happ grouped by DNA and by file type
+/// -- trend is computed from snapshots taken within last `days` days, by default 30
+#[get("/hosted/<id>/storage?<days>")]
+pub async fn happ_storage(
+    id: &str,
+    days: Option<u64>,
+    wsm: &State<WsMutex>,
+) -> Result<Json<storage::StorageReport>, (Status, String)> {
+    let id = ActionHashB64::from_b64_str(id).map_err(|e| (Status::BadRequest, e.to_string()))?;
+    let days = days.unwrap_or(30);
+    let since = Utc::now()
+        .checked_sub_days(Days::new(days))
+        .ok_or_else(|| {
+            (
+                Status::BadRequest,
+                format!("Range of {} days is too long", days),
+            )
+        })?;
+
+    let mut ws = wsm.lock().await;
+
+    Ok(Json(
+        storage::handle_get_storage(&mut ws, id, since)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?,
+    ))
+}
+
+/// Returns earnings of a happ in time range split into buckets of a day, week or month
+/// -- `from` and `to` are dates (`2024-07-01`) or RFC 3339 date-times, range defaults to last 30 days
+/// -- ranges of more than MAX_BUCKETS buckets are rejected with 400
+#[get("/hosted/<id>/earnings?<from>&<to>&<bucket>")]
+pub async fn happ_earnings(
+    id: &str,
+    from: Option<&str>,
+    to: Option<&str>,
+    bucket: Option<Bucket>,
+    wsm: &State<WsMutex>,
+) -> Result<Json<Vec<EarningsPoint>>, (Status, String)> {
+    let id = ActionHashB64::from_b64_str(id).map_err(|e| (Status::BadRequest, e.to_string()))?;
+    let (from, to) = parse_range(from, to, 30).map_err(|e| (Status::BadRequest, e.to_string()))?;
+    let buckets = Buckets::new(from, to, bucket.unwrap_or(Bucket::Day))
+        .map_err(|e| (Status::BadRequest, e.to_string()))?;
+
+    let mut ws = wsm.lock().await;
+    Ok(Json(
+        handle_get_earnings_series(&mut ws, id, &buckets)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?,
+    ))
+}
+
+/// Returns usage of a happ in time range split into buckets of an hour or a day
+/// -- `from` and `to` are dates (`2024-07-01`) or RFC 3339 date-times, range defaults to last 7 days
+/// -- ranges of more than MAX_BUCKETS buckets are rejected with 400
+#[get("/hosted/<id>/usage/series?<from>&<to>&<bucket>")]
+pub async fn usage_series(
+    id: &str,
+    from: Option<&str>,
+    to: Option<&str>,
+    bucket: Option<Bucket>,
+    wsm: &State<WsMutex>,
+) -> Result<Json<Vec<UsagePoint>>, (Status, String)> {
+    let id = ActionHashB64::from_b64_str(id).map_err(|e| (Status::BadRequest, e.to_string()))?;
+    let (from, to) = parse_range(from, to, 7).map_err(|e| (Status::BadRequest, e.to_string()))?;
+    let buckets = Buckets::new(from, to, bucket.unwrap_or(Bucket::Day))
+        .map_err(|e| (Status::BadRequest, e.to_string()))?;
+
+    let mut ws = wsm.lock().await;
+    Ok(Json(
+        handle_get_usage_series(&mut ws, id, &buckets)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?,
+    ))
+}
+
+/// Returns host's prices for happ next to publisher's prices and host's default prices
+#[get("/hosted/<id>/preferences")]
+pub async fn get_preferences(
+    id: &str,
+    wsm: &State<WsMutex>,
+) -> Result<Json<preferences::HappPreferencesResponse>, (Status, String)> {
+    let mut ws = wsm.lock().await;
+
+    let id = ActionHashB64::from_b64_str(id).map_err(|e| (Status::BadRequest, e.to_string()))?;
+
+    Ok(Json(
+        preferences::handle_get_happ_preferences(&mut ws, id)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?,
+    ))
+}
+
+/// Overrides host's default prices for happ
+/// -- prices can not exceed ones from happ's publisher pricing preferences
+#[put("/hosted/<id>/preferences", format = "json", data = "<payload>")]
+pub async fn set_preferences(
+    id: &str,
+    payload: Json<install::HappPreferences>,
+    wsm: &State<WsMutex>,
+) -> Result<(), (Status, String)> {
+    let mut ws = wsm.lock().await;
+
+    let id = ActionHashB64::from_b64_str(id).map_err(|e| (Status::BadRequest, e.to_string()))?;
+    let payload = payload.into_inner();
+
+    let publisher_pricing_pref = preferences::get_publisher_pricing_pref(&mut ws, id.clone())
+        .await
+        .map_err(|e| (Status::InternalServerError, e.to_string()))?;
+    preferences::validate_happ_preferences(&payload, &publisher_pricing_pref)
+        .map_err(|e| (Status::BadRequest, e.to_string()))?;
+
+    preferences::handle_set_happ_preferences(&mut ws, id, payload)
         .await
         .map_err(|e| (Status::InternalServerError, e.to_string()))
 }
 
+/// Returns servicelogger entries of a happ
+/// -- filtered by time range (`from`/`to` or last `days` days, defaults to 7 days,
+/// `days=0` is today since midnight UTC), `entry_type`, web user's `agent`, `status_code`, `zome`,
+/// `function` and `source_ip` -- `days` reaching before the earliest representable date is rejected with 400
+/// -- all entries are returned oldest first, unless a page is requested with `limit` or `cursor`
+/// -- pages are ordered newest first, next page is requested by passing `nextCursor` of the previous page as `cursor`
+#[get("/hosted/<id>/logs?<query..>")]
+pub async fn logs(
+    id: &str,
+    query: LogsQuery,
+    wsm: &State<WsMutex>,
+) -> Result<Json<LogsResponse>, (Status, String)> {
+    let mut ws = wsm.lock().await;
+
+    let id = ActionHashB64::from_b64_str(id).map_err(|e| (Status::BadRequest, e.to_string()))?;
+    let paged = query.limit.is_some() || query.cursor.is_some();
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    let cursor = query.cursor;
+    let filter = to_log_filter(query)?;
+
+    if !paged {
+        let page = handle_get_service_logs(&mut ws, id, filter, None, usize::MAX)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?;
+        return Ok(Json(LogsResponse::All(
+            page.entries.into_iter().rev().map(|e| e.entry).collect(),
+        )));
+    }
+
+    Ok(Json(LogsResponse::Page(
+        handle_get_service_logs(&mut ws, id, filter, cursor, limit)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?,
+    )))
+}
+
+/// Streams activity logs of hosted happ `id` in one of the formats understood by web log analysers
+/// -- `format` is one of `clf` (Common Log Format), `combined` (Combined Log Format), `ndjson` or `csv`
+/// -- logs are filtered with the same query params as in `/hosted/<id>/logs` except `cursor` and `limit`
+/// -- lines are ordered oldest first
+#[get("/hosted/<id>/logs/export?<format>&<query..>")]
+pub async fn export_logs(
+    id: &str,
+    format: ExportFormat,
+    query: LogsQuery,
+    wsm: &State<WsMutex>,
+) -> Result<(ContentType, TextStream![String]), (Status, String)> {
+    let id = ActionHashB64::from_b64_str(id).map_err(|e| (Status::BadRequest, e.to_string()))?;
+    let filter = to_log_filter(query)?;
+
+    let records = {
+        let mut ws = wsm.lock().await;
+        handle_export_service_logs(&mut ws, id, filter)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?
+    };
+
+    // lines are formatted as the response is streamed, lock on ws is already released
+    Ok((
+        format.content_type(),
+        TextStream! {
+            if format == ExportFormat::Csv {
+                yield format!("{}\n", CSV_HEADER);
+            }
+            for record in records {
+                if let Some(line) = to_weblog_line(&record, format) {
+                    yield line;
+                }
+            }
+        },
+    ))
+}
+
+fn to_log_filter(query: LogsQuery) -> Result<LogFilter, (Status, String)> {
+    let days = query.days.unwrap_or(7).max(0) as u64;
+    // `days=0` is today since midnight UTC, a range of 0 days would be empty
+    let today = Utc::now().format("%Y-%m-%d").to_string();
+    let from = match query.from.as_deref() {
+        None if days == 0 => Some(today.as_str()),
+        from => from,
+    };
+    let (from, to) = parse_range(from, query.to.as_deref(), days)
+        .map_err(|e| (Status::BadRequest, e.to_string()))?;
+
+    Ok(LogFilter {
+        from,
+        to,
+        entry_type: query.entry_type,
+        agent: query.agent,
+        status_code: query.status_code,
+        zome: query.zome,
+        function: query.function,
+        source_ip: query.source_ip,
+    })
+}
+
+/// Starts installation of a happ in the background and returns its install job right away
+/// -- progress of installation is reported at `/hosted/install/<job>`.
+/// If the happ is already being installed, its running job is returned instead.
+#[post("/hosted/install", format = "application/json", data = "<payload>")]
+pub async fn install_app(
+    wsm: &State<WsMutex>,
+    jobs: &State<InstallJobsMutex>,
+    payload: install::InstallHappBody,
+) -> Result<Json<InstallJob>, (Status, String)> {
+    // Validate format of happ id before starting a job
+    ActionHashB64::from_b64_str(&payload.happ_id)
+        .map_err(|e| (Status::BadRequest, e.to_string()))?;
+
+    let (job, created) = jobs.lock().await.create(&payload.happ_id);
+    if !created {
+        return Ok(Json(job));
+    }
+
+    let progress = JobProgress::new(job.id, jobs.inner().clone());
+    let wsm = wsm.inner().clone();
+
+    rocket::tokio::spawn(async move {
+        if let Err(e) = install::handle_install_app(&wsm, payload, &progress).await {
+            warn!("install job failed: {}", e);
+        }
+    });
+
+    Ok(Json(job))
+}
+
+/// Lists install jobs started since the API was launched, newest first
+#[get("/hosted/install")]
+pub async fn install_jobs(jobs: &State<InstallJobsMutex>) -> Json<Vec<InstallJob>> {
+    Json(jobs.lock().await.list())
+}
+
+/// Reports phase, progress and error of an install job
+/// -- ranked below `/hosted/<id>/...` routes, which can never match a numeric job id
+#[get("/hosted/install/<job>", rank = 1)]
+pub async fn install_job(
+    job: u64,
+    jobs: &State<InstallJobsMutex>,
+) -> Result<Json<InstallJob>, (Status, String)> {
+    match jobs.lock().await.get(job) {
+        Some(job) => Ok(Json(job.clone())),
+        None => Err((
+            Status::NotFound,
+            format!("Install job {} does not exist", job),
+        )),
+    }
+}
+
+/// Requests cancellation of an install job, installation stops before its next phase and is rolled back
+#[post("/hosted/install/<job>/cancel")]
+pub async fn cancel_install_job(
+    job: u64,
+    jobs: &State<InstallJobsMutex>,
+) -> Result<Json<InstallJob>, (Status, String)> {
+    Ok(Json(
+        jobs.lock()
+            .await
+            .cancel(job)
+            .map_err(|e| (Status::BadRequest, e.to_string()))?,
+    ))
+}
+
 #[post("/hosted/register", format = "application/json", data = "<payload>")]
 pub async fn register_app(
     wsm: &State<WsMutex>,
     payload: HappInput,
 ) -> Result<Json<PresentedHappBundle>, (Status, String)> {
+    register::validate_happ_input(&payload)
+        .await
+        .map_err(|e| (Status::BadRequest, e.to_string()))?;
+
     let mut ws = wsm.lock().await;
-    if payload.name.is_empty() {
-        return Err((Status::BadRequest, "name is empty".to_string()));
-    }
-    if payload.bundle_url.is_empty() {
-        return Err((Status::BadRequest, "bundle_url is empty".to_string()));
-    }
 
     Ok(Json(
         register::handle_register_app(&mut ws, payload)
@@ -127,6 +551,141 @@ pub async fn register_app(
     ))
 }
 
+/// Updates a happ published from this holoport in hha, payload is validated the same way as on registration
+#[put("/hosted/<id>", format = "application/json", data = "<payload>")]
+pub async fn update_app(
+    id: &str,
+    wsm: &State<WsMutex>,
+    payload: HappInput,
+) -> Result<Json<PresentedHappBundle>, (Status, String)> {
+    let id = ActionHashB64::from_b64_str(id).map_err(|e| (Status::BadRequest, e.to_string()))?;
+    register::validate_happ_input(&payload)
+        .await
+        .map_err(|e| (Status::BadRequest, e.to_string()))?;
+
+    let mut ws = wsm.lock().await;
+
+    Ok(Json(
+        register::handle_update_app(&mut ws, id, payload)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?,
+    ))
+}
+
+/// Disk quotas of hosted happs, in bytes
+#[get("/hosted/quotas")]
+pub async fn get_quotas() -> Result<Json<quota::DiskQuotas>, (Status, String)> {
+    Ok(Json(quota::handle_get_quotas().map_err(|e| {
+        (Status::InternalServerError, e.to_string())
+    })?))
+}
+
+#[put("/hosted/quotas", format = "json", data = "<payload>")]
+pub async fn set_quotas(
+    payload: Json<quota::DiskQuotas>,
+) -> Result<Json<quota::DiskQuotas>, (Status, String)> {
+    Ok(Json(
+        quota::handle_set_quotas(payload.into_inner())
+            .map_err(|e| (Status::BadRequest, e.to_string()))?,
+    ))
+}
+
+/// Checks disk usage of enabled happs against quotas right away, happs over quota are auto-disabled
+#[post("/hosted/quotas/check")]
+pub async fn check_quotas(
+    wsm: &State<WsMutex>,
+) -> Result<Json<quota::QuotaReport>, (Status, String)> {
+    let mut ws = wsm.lock().await;
+
+    Ok(Json(quota::handle_check_quotas(&mut ws).await.map_err(
+        |e| (Status::InternalServerError, e.to_string()),
+    )?))
+}
+
+/// Rules by which happs are hidden from or pinned to the top of host's views
+#[get("/hosted/visibility")]
+pub async fn get_visibility() -> Result<Json<visibility::VisibilityRules>, (Status, String)> {
+    Ok(Json(visibility::handle_get_rules().map_err(|e| {
+        (Status::InternalServerError, e.to_string())
+    })?))
+}
+
+#[put("/hosted/visibility", format = "json", data = "<payload>")]
+pub async fn set_visibility(
+    payload: Json<visibility::VisibilityRules>,
+) -> Result<Json<visibility::VisibilityRules>, (Status, String)> {
+    Ok(Json(
+        visibility::handle_set_rules(payload.into_inner())
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?,
+    ))
+}
+
+/// Policy by which newly published happs are enabled without host's action
+#[get("/hosted/auto_accept")]
+pub async fn get_auto_accept() -> Result<Json<auto_accept::AutoAcceptPolicy>, (Status, String)> {
+    Ok(Json(auto_accept::handle_get_policy().map_err(|e| {
+        (Status::InternalServerError, e.to_string())
+    })?))
+}
+
+#[put("/hosted/auto_accept", format = "json", data = "<payload>")]
+pub async fn set_auto_accept(
+    payload: Json<auto_accept::AutoAcceptPolicy>,
+) -> Result<Json<auto_accept::AutoAcceptPolicy>, (Status, String)> {
+    Ok(Json(
+        auto_accept::handle_set_policy(payload.into_inner())
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?,
+    ))
+}
+
+/// Evaluates happs host has not decided on right away, matching happs are installed before response is sent
+#[post("/hosted/auto_accept/run")]
+pub async fn run_auto_accept(
+    wsm: &State<WsMutex>,
+    jobs: &State<InstallJobsMutex>,
+    hbs: &State<HbSMutex>,
+) -> Result<Json<Vec<auto_accept::AutoAcceptDecision>>, (Status, String)> {
+    let mut hbs = hbs.lock().await;
+
+    Ok(Json(
+        auto_accept::handle_run_auto_accept(wsm, jobs, &mut hbs)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?,
+    ))
+}
+
+/// Lists auto-accept decisions, newest first
+#[get("/hosted/auto_accept/log")]
+pub async fn auto_accept_log(
+) -> Result<Json<Vec<auto_accept::AutoAcceptDecision>>, (Status, String)> {
+    Ok(Json(auto_accept::handle_get_log().map_err(|e| {
+        (Status::InternalServerError, e.to_string())
+    })?))
+}
+
+/// Returns history of enabling and disabling happ on this holoport
+#[get("/hosted/<id>/history")]
+pub async fn happ_hosting_history(
+    id: &str,
+    wsm: &State<WsMutex>,
+) -> Result<Json<hosting_history::HostingHistory>, (Status, String)> {
+    let mut ws = wsm.lock().await;
+
+    let id = ActionHashB64::from_b64_str(id).map_err(|e| (Status::BadRequest, e.to_string()))?;
+    let app_connection = ws
+        .get_connection(ws.core_app_id.clone())
+        .await
+        .map_err(|e| (Status::InternalServerError, e.to_string()))?;
+    let happ = install::get_app_details(app_connection, id.clone().into())
+        .await
+        .map_err(|e| (Status::InternalServerError, e.to_string()))?;
+    hosting_history::sync_with_hha(&happ, None);
+
+    Ok(Json(hosting_history::handle_get_history(&id).map_err(
+        |e| (Status::InternalServerError, e.to_string()),
+    )?))
+}
+
 // Types
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
@@ -138,9 +697,13 @@ pub struct HappDetails {
     pub categories: Vec<String>,
     pub enabled: bool,
     pub is_auto_disabled: bool,
+    pub auto_disabled_reason: Option<String>, // recorded when the happ was auto-disabled by this API
     pub is_paused: bool,
+    pub is_pinned: bool, // pinned by host's visibility rules, see handlers::visibility
     pub source_chains: Option<u16>,
-    pub days_hosted: Option<u16>,
+    pub days_hosted: Option<u16>, // since happ was first enabled on this holoport
+    pub first_enabled: Option<Timestamp>,
+    pub enabled_time: Option<u64>, // seconds happ has been enabled for in total
     pub earnings: Option<Earnings>,
     pub usage: Option<HappStats>,
     pub hosting_plan: Option<HostingPlan>,
@@ -154,28 +717,42 @@ impl HappDetails {
         usage_interval: i64,
         ws: &mut Ws,
     ) -> Self {
+        let auto_disabled_reason = quota::get_auto_disabled_reason(&happ.id);
+        // invoices are issued only for hosting, so the first one is the latest possible start of it
+        let first_invoice = transactions.iter().map(|tx| tx.created_date).min();
+        hosting_history::sync_with_hha(happ, first_invoice);
+        let history = hosting_history::handle_get_history(&happ.id)
+            .map(Some)
+            .unwrap_or_else(|e| {
+                warn!("error reading hosting history of happ {}: {}", &happ.id, e);
+                None
+            });
+        let first_enabled = history.as_ref().and_then(|history| history.first_enabled);
         HappDetails {
             id: happ.id.clone(),
             name: happ.name.clone(),
             description: happ.name.clone(),
             categories: happ.categories.clone(),
             enabled: happ.host_settings.is_enabled,
-            is_auto_disabled: happ.host_settings.is_auto_disabled,
+            is_auto_disabled: happ.host_settings.is_auto_disabled || auto_disabled_reason.is_some(),
+            auto_disabled_reason,
             is_paused: happ.is_paused,
+            is_pinned: false,
             source_chains: count_instances(happ.id.clone(), ws)
                 .await
                 .unwrap_or_else(|e| {
                     warn!("error counting instances for happ {}: {}", &happ.id, e);
                     None
                 }),
-            days_hosted: count_days_hosted(happ.last_edited).unwrap_or_else(|e| {
-                warn!("error counting earnings for happ {}: {}", &happ.id, e);
-                None
-            }),
-            earnings: count_earnings(transactions).await.unwrap_or_else(|e| {
-                warn!("error counting earnings for happ {}: {}", &happ.id, e);
-                None
-            }),
+            days_hosted: history.as_ref().and_then(|history| history.days_hosted),
+            first_enabled,
+            enabled_time: history.map(|history| history.enabled_time),
+            earnings: count_earnings(transactions, first_enabled)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("error counting earnings for happ {}: {}", &happ.id, e);
+                    None
+                }),
             usage: get_usage(happ.id.clone(), usage_interval, ws)
                 .await
                 .unwrap_or_else(|e| {
@@ -220,64 +797,14 @@ pub struct HappStats {
     pub disk_usage: u64,
 }
 
-#[derive(Serialize, Deserialize)]
-#[serde(crate = "rocket::serde")]
-#[serde(rename_all = "camelCase")]
-pub enum HostingPlan {
-    Free,
-    Paid,
-}
-
-impl fmt::Display for HostingPlan {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            HostingPlan::Free => write!(f, "free"),
-            HostingPlan::Paid => write!(f, "paid"),
-        }
-    }
-}
-
 #[derive(Serialize, Deserialize, Debug, Clone, SerializedBytes)]
 pub struct UsageTimeInterval {
     pub duration_unit: String,
     pub amount: i64,
 }
 
-// return type of a zome call to hha/get_happ_preferences
-#[derive(Debug, Serialize, Deserialize, SerializedBytes, Clone)]
-pub struct ServiceloggerHappPreferences {
-    pub provider_pubkey: AgentPubKey,
-    pub max_fuel_before_invoice: Fuel,
-    pub price_compute: Fuel,
-    pub price_storage: Fuel,
-    pub price_bandwidth: Fuel,
-    pub max_time_before_invoice: Duration,
-}
-
 // helper functions
 
-pub async fn get_plan(happ_id: ActionHashB64, ws: &mut Ws) -> Result<Option<HostingPlan>> {
-    let app_connection = ws.get_connection(ws.core_app_id.clone()).await?;
-
-    let s: ServiceloggerHappPreferences = app_connection
-        .zome_call_typed(
-            CoreAppRoleName::HHA.into(),
-            "hha".into(),
-            "get_happ_preferences".into(),
-            happ_id,
-        )
-        .await?;
-
-    if s.price_compute == Fuel::new(0)
-        && s.price_storage == Fuel::new(0)
-        && s.price_bandwidth == Fuel::new(0)
-    {
-        Ok(Some(HostingPlan::Free))
-    } else {
-        Ok(Some(HostingPlan::Paid))
-    }
-}
-
 pub async fn count_instances(happ_id: ActionHashB64, ws: &mut Ws) -> Result<Option<u16>> {
     // What filter shall I use in list_happs()? Is None correct?
     Ok(Some(
@@ -296,23 +823,45 @@ pub async fn count_instances(happ_id: ActionHashB64, ws: &mut Ws) -> Result<Opti
     ))
 }
 
-// TODO: average_weekly still needs to be calculated - from total and days_hosted?
-pub async fn count_earnings(transactions: Vec<Transaction>) -> Result<Option<Earnings>> {
+/// Sums up earnings from hosting invoices of a happ.
+/// Weekly average is taken over the weeks since the earlier of `hosted_since` (first enable from hosting history)
+/// and the first invoice issued for the happ, at least one week.
+pub async fn count_earnings(
+    transactions: Vec<Transaction>,
+    hosted_since: Option<Timestamp>,
+) -> Result<Option<Earnings>> {
     let mut e = Earnings::default();
+    let week = Duration::from_secs(7 * 24 * 60 * 60);
+    let week_ago = (Timestamp::now() - week)?;
+    let mut earning_since = hosted_since;
+
     for p in transactions.iter() {
         let amount_fuel = Fuel::from_str(&p.amount)?;
         e.total = (e.total + amount_fuel)?;
+
+        if earning_since.map_or(true, |since| p.created_date < since) {
+            earning_since = Some(p.created_date);
+        }
+
         // if completed_date is within last week then add fuel to last_7_days, too
-        let week = Duration::from_secs(7 * 24 * 60 * 60);
-        if (Timestamp::now() - week)? < p.completed_date.unwrap() {
-            e.last_7_days = (e.last_7_days + amount_fuel)?
-        };
+        match p.completed_date {
+            Some(completed_date) => {
+                if week_ago < completed_date {
+                    e.last_7_days = (e.last_7_days + amount_fuel)?
+                }
+            }
+            None => warn!("completed transaction {} has no completed_date", p.id),
+        }
+    }
+
+    if let Some(since) = earning_since {
+        let weeks = ((Timestamp::now() - since)?.num_days().max(0) as u64)
+            .div_ceil(7)
+            .max(1);
+        e.average_weekly = fuel::divide(e.total, weeks)?;
     }
-    Ok(Some(e))
-}
 
-pub fn count_days_hosted(since: Timestamp) -> Result<Option<u16>> {
-    Ok(Some((Timestamp::now() - since)?.num_days() as u16))
+    Ok(Some(e))
 }
 
 async fn get_usage(
@@ -0,0 +1,19 @@
+use crate::handlers::installed::{self, AppStatus, InstalledApp};
+use crate::hpos::WsMutex;
+use rocket::{get, http::Status, serde::json::Json, State};
+
+/// Lists apps installed in holochain with their cells and the hosted happ they belong to
+/// -- with `status`, only apps with given status are listed
+#[get("/installed?<status>")]
+pub async fn installed_apps(
+    status: Option<AppStatus>,
+    wsm: &State<WsMutex>,
+) -> Result<Json<Vec<InstalledApp>>, (Status, String)> {
+    let mut ws = wsm.lock().await;
+
+    Ok(Json(
+        installed::handle_get_installed(&mut ws, status)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?,
+    ))
+}
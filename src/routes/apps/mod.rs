@@ -1,3 +1,8 @@
+pub mod bundles;
 pub mod call_zome;
 pub mod core;
 pub mod hosted;
+pub mod installed;
+pub mod published;
+pub mod reconcile;
+pub mod self_hosted;
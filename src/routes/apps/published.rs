@@ -0,0 +1,85 @@
+use crate::common::types::{HappInput, PresentedHappBundle};
+use crate::handlers::{
+    published::{self, PublishedHapp, PublisherAction},
+    register,
+};
+use crate::hpos::WsMutex;
+use holochain_types::dna::ActionHashB64;
+use rocket::{
+    http::Status,
+    serde::json::Json,
+    {get, post, State},
+};
+
+/// Lists happs published from this holoport, drafts included
+#[get("/published")]
+pub async fn get_published(
+    wsm: &State<WsMutex>,
+) -> Result<Json<Vec<PublishedHapp>>, (Status, String)> {
+    let mut ws = wsm.lock().await;
+
+    Ok(Json(
+        published::handle_get_published(&mut ws)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?,
+    ))
+}
+
+/// Stores a happ in hha as a draft, payload is validated the same way as on registration
+#[post("/published/draft", format = "application/json", data = "<payload>")]
+pub async fn create_draft(
+    wsm: &State<WsMutex>,
+    payload: HappInput,
+) -> Result<Json<PresentedHappBundle>, (Status, String)> {
+    register::validate_happ_input(&payload)
+        .await
+        .map_err(|e| (Status::BadRequest, e.to_string()))?;
+
+    let mut ws = wsm.lock().await;
+
+    Ok(Json(
+        published::handle_create_draft(&mut ws, payload)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?,
+    ))
+}
+
+#[post("/published/<id>/publish")]
+pub async fn publish_draft(
+    id: &str,
+    wsm: &State<WsMutex>,
+) -> Result<Json<PresentedHappBundle>, (Status, String)> {
+    run_publisher_action(id, wsm, PublisherAction::Publish).await
+}
+
+#[post("/published/<id>/pause")]
+pub async fn pause_published(
+    id: &str,
+    wsm: &State<WsMutex>,
+) -> Result<Json<PresentedHappBundle>, (Status, String)> {
+    run_publisher_action(id, wsm, PublisherAction::Pause).await
+}
+
+#[post("/published/<id>/unpause")]
+pub async fn unpause_published(
+    id: &str,
+    wsm: &State<WsMutex>,
+) -> Result<Json<PresentedHappBundle>, (Status, String)> {
+    run_publisher_action(id, wsm, PublisherAction::Unpause).await
+}
+
+async fn run_publisher_action(
+    id: &str,
+    wsm: &State<WsMutex>,
+    action: PublisherAction,
+) -> Result<Json<PresentedHappBundle>, (Status, String)> {
+    let mut ws = wsm.lock().await;
+
+    let id = ActionHashB64::from_b64_str(id).map_err(|e| (Status::BadRequest, e.to_string()))?;
+
+    Ok(Json(
+        published::handle_publisher_action(&mut ws, id, action)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?,
+    ))
+}
@@ -0,0 +1,49 @@
+use crate::handlers::reconcile::{self, DriftReport, ReconcilerMutex, RepairAction};
+use crate::hpos::WsMutex;
+use rocket::{
+    http::Status,
+    serde::json::Json,
+    {get, post, State},
+};
+
+/// Reports drift between hosting state in hha and apps in holochain found by the last reconciliation
+/// -- with `refresh=true`, or when reconciliation has not run yet, drift is checked right away
+#[get("/reconcile?<refresh>")]
+pub async fn reconcile_report(
+    refresh: Option<bool>,
+    wsm: &State<WsMutex>,
+    reconciler: &State<ReconcilerMutex>,
+) -> Result<Json<DriftReport>, (Status, String)> {
+    if !refresh.unwrap_or(false) {
+        if let Some(report) = reconciler.lock().await.last_report() {
+            return Ok(Json(report));
+        }
+    }
+
+    Ok(Json(
+        reconcile::handle_reconcile(wsm, reconciler, false)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?,
+    ))
+}
+
+/// Checks drift and repairs it by installing, enabling or disabling apps in holochain
+#[post("/reconcile/repair")]
+pub async fn reconcile_repair(
+    wsm: &State<WsMutex>,
+    reconciler: &State<ReconcilerMutex>,
+) -> Result<Json<DriftReport>, (Status, String)> {
+    Ok(Json(
+        reconcile::handle_reconcile(wsm, reconciler, true)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?,
+    ))
+}
+
+/// Lists repair actions taken by reconciliation, newest first
+#[get("/reconcile/log")]
+pub async fn reconcile_log() -> Result<Json<Vec<RepairAction>>, (Status, String)> {
+    Ok(Json(reconcile::handle_get_reconcile_log().map_err(
+        |e| (Status::InternalServerError, e.to_string()),
+    )?))
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::fs;
+
+use crate::handlers::self_hosted::{self, SelfHostedHapp, SelfHostedInstall};
+use crate::hpos::{get_data_dir, WsMutex};
+use holochain_types::prelude::{MembraneProof, RoleName, Timestamp};
+use log::warn;
+use rocket::{
+    form::Form,
+    fs::TempFile,
+    http::Status,
+    serde::json::{serde_json, Json},
+    FromForm, {delete, get, post, State},
+};
+
+/// Multipart form of self-hosted happ installation, either `bundle` or `path` has to be provided
+#[derive(FromForm)]
+pub struct SelfHostedForm<'r> {
+    installed_app_id: String,
+    bundle: Option<TempFile<'r>>, // uploaded .happ file
+    path: Option<String>,         // path to .happ file inside HPOS_API_DATA_DIR
+    network_seed: Option<String>,
+    membrane_proofs: Option<String>, // json object of role name to membrane proof
+}
+
+/// Installs a happ that is not registered in hha from an uploaded bundle or a local path.
+/// Invalid form fields are reported with 400 before anything is installed.
+#[post("/self_hosted", data = "<form>")]
+pub async fn install_self_hosted(
+    form: Form<SelfHostedForm<'_>>,
+    wsm: &State<WsMutex>,
+) -> Result<Json<SelfHostedHapp>, (Status, String)> {
+    let SelfHostedForm {
+        installed_app_id,
+        bundle,
+        path,
+        network_seed,
+        membrane_proofs,
+    } = form.into_inner();
+
+    let membrane_proofs: HashMap<RoleName, MembraneProof> = match membrane_proofs {
+        Some(json) if !json.trim().is_empty() => serde_json::from_str(&json).map_err(|e| {
+            (
+                Status::BadRequest,
+                format!("Invalid membrane_proofs: {}", e),
+            )
+        })?,
+        _ => HashMap::new(),
+    };
+    let network_seed = network_seed.filter(|seed| !seed.is_empty());
+
+    let core_app_id = wsm.lock().await.core_app_id.clone();
+    self_hosted::validate_installed_app_id(&installed_app_id, &core_app_id)
+        .map_err(|e| (Status::BadRequest, e.to_string()))?;
+
+    let (bundle_path, source, uploaded) = match (bundle, path) {
+        (Some(mut bundle), None) => {
+            let source = bundle.name().unwrap_or("upload").to_string();
+            let upload_dir = self_hosted::get_upload_dir();
+            fs::create_dir_all(&upload_dir)
+                .map_err(|e| (Status::InternalServerError, e.to_string()))?;
+            let path = upload_dir.join(format!("{}.happ", Timestamp::now().as_micros()));
+            bundle
+                .copy_to(&path)
+                .await
+                .map_err(|e| (Status::InternalServerError, e.to_string()))?;
+            (path, source, true)
+        }
+        (None, Some(path)) => {
+            let bundle_path = self_hosted::resolve_bundle_path(&path, &get_data_dir())
+                .map_err(|e| (Status::BadRequest, e.to_string()))?;
+            (bundle_path, path, false)
+        }
+        _ => {
+            return Err((
+                Status::BadRequest,
+                "Exactly one of `bundle` and `path` has to be provided".to_string(),
+            ))
+        }
+    };
+
+    let payload = SelfHostedInstall {
+        installed_app_id,
+        bundle_path: bundle_path.clone(),
+        source,
+        network_seed,
+        membrane_proofs,
+    };
+    let result = self_hosted::handle_install_self_hosted(&mut *wsm.lock().await, payload).await;
+
+    // holochain keeps its own copy of installed bundle
+    if uploaded {
+        if let Err(e) = fs::remove_file(&bundle_path) {
+            warn!("Failed to remove uploaded bundle {:?}: {}", bundle_path, e);
+        }
+    }
+
+    Ok(Json(result.map_err(|e| {
+        (Status::InternalServerError, e.to_string())
+    })?))
+}
+
+/// Lists self-hosted happs with their status in holochain
+#[get("/self_hosted")]
+pub async fn get_self_hosted(
+    wsm: &State<WsMutex>,
+) -> Result<Json<Vec<SelfHostedHapp>>, (Status, String)> {
+    let mut ws = wsm.lock().await;
+
+    Ok(Json(
+        self_hosted::handle_list_self_hosted(&mut ws)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.to_string()))?,
+    ))
+}
+
+#[post("/self_hosted/<id>/enable")]
+pub async fn enable_self_hosted(id: &str, wsm: &State<WsMutex>) -> Result<(), (Status, String)> {
+    let mut ws = wsm.lock().await;
+
+    self_hosted::handle_enable_self_hosted(&mut ws, id)
+        .await
+        .map_err(|e| (Status::InternalServerError, e.to_string()))
+}
+
+#[post("/self_hosted/<id>/disable")]
+pub async fn disable_self_hosted(id: &str, wsm: &State<WsMutex>) -> Result<(), (Status, String)> {
+    let mut ws = wsm.lock().await;
+
+    self_hosted::handle_disable_self_hosted(&mut ws, id)
+        .await
+        .map_err(|e| (Status::InternalServerError, e.to_string()))
+}
+
+/// Uninstalls self-hosted happ together with its data
+#[delete("/self_hosted/<id>")]
+pub async fn remove_self_hosted(id: &str, wsm: &State<WsMutex>) -> Result<(), (Status, String)> {
+    let mut ws = wsm.lock().await;
+
+    self_hosted::handle_remove_self_hosted(&mut ws, id)
+        .await
+        .map_err(|e| (Status::InternalServerError, e.to_string()))
+}
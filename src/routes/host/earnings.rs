@@ -11,14 +11,15 @@ use rocket::{
     State,
 };
 
-use crate::{common::types::RedemptionState, hpos::WsMutex};
+use crate::{common::types::RedemptionState, handlers::visibility, hpos::WsMutex};
 use crate::{
     common::types::{Ledger, Transaction},
     hpos::Ws,
 };
 
 use crate::routes::host::shared::{
-    get_hosting_invoices, HostingInvoicesResponse, InvoiceSet, TransactionAndInvoiceDetails,
+    get_hosting_invoices, get_invoice_happ_id, HostingInvoicesResponse, InvoiceSet,
+    TransactionAndInvoiceDetails,
 };
 
 /// Returns overview of host earnings as needed for the host-console-ui dashboard page
@@ -41,6 +42,9 @@ pub async fn earnings(
 }
 
 async fn handle_earnings(ws: &mut Ws, quantity: u16) -> Result<HostEarningsResponse> {
+    // invoices of happs hidden by host's visibility rules are not counted in
+    let hidden_happ_ids = visibility::get_hidden_happ_ids(ws).await?;
+
     let core_app_connection: &mut AppConnection =
         ws.get_connection(ws.core_app_id.clone()).await.unwrap();
 
@@ -50,13 +54,24 @@ async fn handle_earnings(ws: &mut Ws, quantity: u16) -> Result<HostEarningsRespo
         ..
     } = get_hosting_invoices(core_app_connection.to_owned(), InvoiceSet::All).await?;
 
+    let paid_hosting_invoices: Vec<Transaction> = paid_hosting_invoices
+        .into_iter()
+        .filter(|tx| match get_invoice_happ_id(tx) {
+            Some(id) => !hidden_happ_ids.contains(&id),
+            None => true,
+        })
+        .collect();
+
+    let transaction_and_invoice_details = transaction_and_invoice_details
+        .into_iter()
+        .filter(|details| !hidden_happ_ids.contains(&details.happ.id));
+
     let transaction_and_invoice_details = if quantity > 0 {
         transaction_and_invoice_details
-            .into_iter()
             .take(quantity.into())
             .collect()
     } else {
-        transaction_and_invoice_details
+        transaction_and_invoice_details.collect()
     };
 
     let earnings = calculate_earnings(paid_hosting_invoices)?;
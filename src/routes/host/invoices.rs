@@ -1,27 +1,59 @@
-use anyhow::Result;
+use std::{cmp::Ordering, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use holochain_types::dna::ActionHashB64;
+use holofuel_types::fuel::Fuel;
 use hpos_hc_connect::AppConnection;
-use rocket::{get, http::Status, serde::json::Json, State};
+use rocket::{
+    get,
+    http::Status,
+    serde::{json::Json, Deserialize, Serialize},
+    FromForm, FromFormField, State,
+};
 
+use crate::common::series::{parse_bound, timestamp_to_date_time};
 use crate::hpos::Ws;
 use crate::hpos::WsMutex;
 use crate::routes::host::shared::{
     get_hosting_invoices, HostingInvoicesResponse, InvoiceSet, TransactionAndInvoiceDetails,
 };
 
-/// Returns list of all host invoices as needed for the host-console-ui invoice page
+/// Returns host invoices as needed for the host-console-ui invoice page
 /// -- includes optional invoice set param to allow querying the invoices by their status
-#[get("/invoices?<invoice_set>")]
+/// -- filtered by creation date (`from`/`to`), `happ_id`, `status`, `min_amount` and due date (`due_from`/`due_to`)
+/// -- all invoices are returned as an array, unless a page is requested with `limit` or `cursor`
+/// -- next page is requested by passing `nextCursor` of the previous page as `cursor`
+#[get("/invoices?<query..>")]
 pub async fn invoices(
     wsm: &State<WsMutex>,
-    invoice_set: Option<InvoiceSet>,
-) -> Result<Json<Vec<TransactionAndInvoiceDetails>>, (Status, String)> {
-    let invoice_set = invoice_set.unwrap_or(InvoiceSet::All);
+    query: InvoicesQuery,
+) -> Result<Json<InvoicesResponse>, (Status, String)> {
+    let invoice_set = query.invoice_set.unwrap_or(InvoiceSet::All);
+    let sort = query.sort.unwrap_or(InvoiceSort::CompletedDate);
+    let order = query.order.unwrap_or(SortOrder::Asc);
+    let paged = query.limit.is_some() || query.cursor.is_some();
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    let cursor = query.cursor.clone();
+    let filter = to_invoice_filter(query).map_err(|e| (Status::BadRequest, e.to_string()))?;
 
     let mut ws = wsm.lock().await;
 
-    Ok(Json(handle_invoices(&mut ws, invoice_set).await.map_err(
-        |e| (Status::InternalServerError, e.to_string()),
-    )?))
+    let mut invoices = handle_invoices(&mut ws, invoice_set)
+        .await
+        .map_err(|e| (Status::InternalServerError, e.to_string()))?;
+
+    invoices.retain(|invoice| filter.matches(invoice));
+    sort_invoices(&mut invoices, sort, order);
+
+    if !paged {
+        return Ok(Json(InvoicesResponse::All(invoices)));
+    }
+
+    Ok(Json(InvoicesResponse::Page(
+        paginate(invoices, cursor.as_deref(), sort, order, limit)
+            .map_err(|e| (Status::BadRequest, e.to_string()))?,
+    )))
 }
 
 async fn handle_invoices(
@@ -38,3 +70,375 @@ async fn handle_invoices(
 
     Ok(transaction_and_invoice_details)
 }
+
+fn to_invoice_filter(query: InvoicesQuery) -> Result<InvoiceFilter> {
+    let parse = |bound: Option<String>, end: bool| bound.map(|s| parse_bound(&s, end)).transpose();
+
+    Ok(InvoiceFilter {
+        from: parse(query.from, false)?,
+        to: parse(query.to, true)?,
+        happ_id: query
+            .happ_id
+            .map(|id| ActionHashB64::from_b64_str(&id))
+            .transpose()?,
+        status: query.status,
+        min_amount: query
+            .min_amount
+            .map(|amount| Fuel::from_str(&amount))
+            .transpose()
+            .map_err(|e| anyhow!("Failed to parse min_amount: {:?}", e))?,
+        due_from: parse(query.due_from, false)?,
+        due_to: parse(query.due_to, true)?,
+    })
+}
+
+fn sort_invoices(
+    invoices: &mut [TransactionAndInvoiceDetails],
+    sort: InvoiceSort,
+    order: SortOrder,
+) {
+    invoices.sort_by(|a, b| Position::of(a, sort).cmp(&Position::of(b, sort), sort, order));
+}
+
+/// Returns up to `limit` invoices that follow position `cursor` in sorted `invoices`.
+/// Cursor is a position rather than an invoice, so it stays valid when its invoice is gone.
+fn paginate(
+    invoices: Vec<TransactionAndInvoiceDetails>,
+    cursor: Option<&str>,
+    sort: InvoiceSort,
+    order: SortOrder,
+    limit: usize,
+) -> Result<InvoicesPage> {
+    let start = match cursor {
+        Some(cursor) => {
+            let cursor = Position::parse(cursor)?;
+            invoices
+                .iter()
+                .position(|invoice| {
+                    Position::of(invoice, sort).cmp(&cursor, sort, order) == Ordering::Greater
+                })
+                .unwrap_or(invoices.len())
+        }
+        None => 0,
+    };
+
+    let has_more = invoices.len() > start + limit;
+    let invoices: Vec<TransactionAndInvoiceDetails> =
+        invoices.into_iter().skip(start).take(limit).collect();
+    let next_cursor = if has_more {
+        invoices
+            .last()
+            .map(|invoice| Position::of(invoice, sort).to_string())
+    } else {
+        None
+    };
+
+    Ok(InvoicesPage {
+        invoices,
+        next_cursor,
+    })
+}
+
+/// Position of an invoice in sorted invoices, its sort key and id.
+/// Invoices with equal sort keys are ordered by id, so that pages are stable.
+struct Position {
+    key: String, // empty for invoices without the sorted date
+    id: String,
+}
+
+impl Position {
+    fn of(invoice: &TransactionAndInvoiceDetails, sort: InvoiceSort) -> Self {
+        let key = match sort {
+            InvoiceSort::CompletedDate => invoice
+                .completed_date
+                .map(|date| date.as_micros().to_string())
+                .unwrap_or_default(),
+            InvoiceSort::CreatedDate => invoice.created_date.as_micros().to_string(),
+            InvoiceSort::DueDate => invoice.invoice_details.due.as_micros().to_string(),
+            InvoiceSort::Amount => invoice.amount.clone(),
+        };
+        Position {
+            key,
+            id: invoice.id.to_string(),
+        }
+    }
+
+    /// Parses cursor of form `<sort key>~<id>`
+    fn parse(cursor: &str) -> Result<Self> {
+        let (key, id) = cursor
+            .split_once('~')
+            .ok_or_else(|| anyhow!("Invalid cursor {}", cursor))?;
+        Ok(Position {
+            key: key.to_string(),
+            id: id.to_string(),
+        })
+    }
+
+    fn cmp(&self, other: &Position, sort: InvoiceSort, order: SortOrder) -> Ordering {
+        let ordering = match sort {
+            InvoiceSort::Amount => {
+                let a = Fuel::from_str(&self.key).ok();
+                let b = Fuel::from_str(&other.key).ok();
+                a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+            }
+            _ => {
+                let a: Option<i64> = self.key.parse().ok();
+                let b: Option<i64> = other.key.parse().ok();
+                a.cmp(&b)
+            }
+        };
+        let ordering = match order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        };
+        ordering.then_with(|| self.id.cmp(&other.id))
+    }
+}
+
+impl std::fmt::Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}~{}", self.key, self.id)
+    }
+}
+
+/// Query params of `/host/invoices`, all of them are optional
+#[derive(FromForm)]
+pub struct InvoicesQuery {
+    pub invoice_set: Option<InvoiceSet>,
+    pub from: Option<String>, // date or RFC 3339 date-time, compared with created_date
+    pub to: Option<String>,   // date or RFC 3339 date-time, compared with created_date
+    pub happ_id: Option<String>,
+    pub status: Option<InvoiceStatus>,
+    pub min_amount: Option<String>,
+    pub due_from: Option<String>, // date or RFC 3339 date-time
+    pub due_to: Option<String>,   // date or RFC 3339 date-time
+    pub sort: Option<InvoiceSort>,
+    pub order: Option<SortOrder>,
+    pub cursor: Option<String>, // `nextCursor` of previous page
+    pub limit: Option<usize>,
+}
+
+/// Payment status of an invoice. `Pending` are the unpaid invoices that are not overdue yet.
+#[derive(Serialize, Deserialize, FromFormField, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub enum InvoiceStatus {
+    Paid,
+    Pending,
+    Overdue,
+}
+
+impl InvoiceStatus {
+    fn of(invoice: &TransactionAndInvoiceDetails) -> Self {
+        if invoice.completed_date.is_some() {
+            InvoiceStatus::Paid
+        } else if invoice.is_overdue {
+            InvoiceStatus::Overdue
+        } else {
+            InvoiceStatus::Pending
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, FromFormField, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub enum InvoiceSort {
+    CompletedDate,
+    CreatedDate,
+    DueDate,
+    Amount,
+}
+
+#[derive(Serialize, Deserialize, FromFormField, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// Criteria an invoice has to meet, ranges include `from` and exclude `to`
+#[derive(Debug, Clone)]
+pub struct InvoiceFilter {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub happ_id: Option<ActionHashB64>,
+    pub status: Option<InvoiceStatus>,
+    pub min_amount: Option<Fuel>,
+    pub due_from: Option<DateTime<Utc>>,
+    pub due_to: Option<DateTime<Utc>>,
+}
+
+impl InvoiceFilter {
+    pub fn matches(&self, invoice: &TransactionAndInvoiceDetails) -> bool {
+        let created = timestamp_to_date_time(invoice.created_date);
+        let due = timestamp_to_date_time(invoice.invoice_details.due);
+
+        self.from.map_or(true, |from| created >= from)
+            && self.to.map_or(true, |to| created < to)
+            && self
+                .happ_id
+                .as_ref()
+                .map_or(true, |id| *id == invoice.happ.id)
+            && self
+                .status
+                .map_or(true, |status| status == InvoiceStatus::of(invoice))
+            && self.min_amount.map_or(true, |min| {
+                Fuel::from_str(&invoice.amount).map_or(false, |amount| amount >= min)
+            })
+            && self.due_from.map_or(true, |from| due >= from)
+            && self.due_to.map_or(true, |to| due < to)
+    }
+}
+
+/// All invoices, or a page of them when paging params are given
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(untagged)]
+pub enum InvoicesResponse {
+    All(Vec<TransactionAndInvoiceDetails>),
+    Page(InvoicesPage),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct InvoicesPage {
+    pub invoices: Vec<TransactionAndInvoiceDetails>,
+    pub next_cursor: Option<String>, // None on the last page
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use holochain_types::{
+        dna::{ActionHashB64, AgentPubKeyB64},
+        prelude::{EntryHash, Timestamp},
+    };
+    use holofuel_types::fuel::Fuel;
+
+    use super::{paginate, sort_invoices, InvoiceFilter, InvoiceSort, InvoiceStatus, SortOrder};
+    use crate::common::types::{TransactionDirection, TransactionStatus, TransactionType};
+    use crate::routes::host::shared::{
+        HappNameAndId, InvoiceDetails, QuantityAndPrice, TransactionAndInvoiceDetails,
+    };
+
+    const DAY: i64 = 24 * 60 * 60 * 1_000_000;
+
+    fn invoice(n: u8, amount: &str, paid: bool, is_overdue: bool) -> TransactionAndInvoiceDetails {
+        let item = || QuantityAndPrice {
+            quantity: 0,
+            price: Fuel::new(0),
+        };
+        TransactionAndInvoiceDetails {
+            id: EntryHash::from_raw_36(vec![n; 36]).into(),
+            amount: amount.to_string(),
+            status: if paid {
+                TransactionStatus::Completed
+            } else {
+                TransactionStatus::Pending
+            },
+            is_overdue,
+            r#type: TransactionType::Request,
+            direction: TransactionDirection::Outgoing,
+            created_date: Timestamp::from_micros(n as i64 * DAY),
+            completed_date: paid.then(|| Timestamp::from_micros((n as i64 + 1) * DAY)),
+            expiration_date: None,
+            counterparty: AgentPubKeyB64::from_b64_str(
+                "uhCAk76ikqpgxdisc5bRJcCY-lOTVB8osHEkiGj8hP4kxA01jSrjC",
+            )
+            .unwrap(),
+            note: "Holo Hosting Invoice for \"Test Happ\"".to_string(),
+            proof_of_service: None,
+            url: None,
+            happ: HappNameAndId {
+                name: "Test Happ".to_string(),
+                id: ActionHashB64::from_b64_str(
+                    "uhCkklkJVx4u17eCaaKg_phRJsHOj9u57v_4cHQR-Bd9tb-vePRyC",
+                )
+                .unwrap(),
+            },
+            invoice_details: InvoiceDetails {
+                start: Timestamp::from_micros(0),
+                end: Timestamp::from_micros(n as i64 * DAY),
+                due: Timestamp::from_micros((n as i64 + 7) * DAY),
+                bandwidth: item(),
+                compute: item(),
+                storage: item(),
+            },
+        }
+    }
+
+    fn filter() -> InvoiceFilter {
+        InvoiceFilter {
+            from: None,
+            to: None,
+            happ_id: None,
+            status: None,
+            min_amount: None,
+            due_from: None,
+            due_to: None,
+        }
+    }
+
+    #[test]
+    fn filters_invoices_by_status_and_amount() {
+        let paid = invoice(1, "10", true, false);
+        let pending = invoice(2, "20", false, false);
+        let overdue = invoice(3, "5", false, true);
+
+        let status = |status| InvoiceFilter {
+            status: Some(status),
+            ..filter()
+        };
+        assert!(status(InvoiceStatus::Paid).matches(&paid));
+        assert!(status(InvoiceStatus::Pending).matches(&pending));
+        assert!(!status(InvoiceStatus::Pending).matches(&overdue));
+        assert!(status(InvoiceStatus::Overdue).matches(&overdue));
+
+        let min_amount = InvoiceFilter {
+            min_amount: Some(Fuel::from_str("10").unwrap()),
+            ..filter()
+        };
+        assert!(min_amount.matches(&paid));
+        assert!(min_amount.matches(&pending));
+        assert!(!min_amount.matches(&overdue));
+    }
+
+    #[test]
+    fn sorts_and_pages_invoices() {
+        let (sort, order) = (InvoiceSort::Amount, SortOrder::Desc);
+        let sorted = || {
+            let mut invoices = vec![
+                invoice(1, "10", true, false),
+                invoice(2, "20", false, false),
+                invoice(3, "5", false, true),
+            ];
+            sort_invoices(&mut invoices, sort, order);
+            invoices
+        };
+        let amounts: Vec<String> = sorted().into_iter().map(|i| i.amount).collect();
+        assert_eq!(amounts, vec!["20", "10", "5"]);
+
+        let page = paginate(sorted(), None, sort, order, 2).unwrap();
+        assert_eq!(page.invoices.len(), 2);
+        let cursor = page.next_cursor.clone().unwrap();
+        assert_eq!(cursor, format!("10~{}", page.invoices[1].id));
+
+        let page = paginate(sorted(), Some(&cursor), sort, order, 2).unwrap();
+        assert_eq!(page.invoices.len(), 1);
+        assert_eq!(page.invoices[0].amount, "5");
+        assert_eq!(page.next_cursor, None);
+
+        // invoice of the cursor has been filtered out meanwhile
+        let mut invoices = sorted();
+        invoices.retain(|invoice| invoice.amount != "10");
+        let page = paginate(invoices, Some(&cursor), sort, order, 2).unwrap();
+        assert_eq!(page.invoices.len(), 1);
+        assert_eq!(page.invoices[0].amount, "5");
+
+        assert!(paginate(vec![], Some("10"), sort, order, 2).is_err());
+    }
+}
@@ -101,9 +101,16 @@ pub async fn get_hosting_invoices(
     })
 }
 
+/// Returns id of the happ a hosting invoice was issued for, as read from its note
+pub fn get_invoice_happ_id(transaction: &Transaction) -> Option<ActionHashB64> {
+    let Note(_, invoice_note) = parse_note(transaction.note.clone())?;
+    ActionHashB64::from_b64_str(&invoice_note.hha_id).ok()
+}
+
 fn get_hosted_happ_invoice_details(
     transactions: Vec<Transaction>,
 ) -> Result<Vec<TransactionAndInvoiceDetails>> {
+    let now = Timestamp::now();
     let mut transaction_and_invoice_details: Vec<TransactionAndInvoiceDetails> = transactions
         .into_iter()
         .map(|transaction| {
@@ -161,6 +168,7 @@ fn get_hosted_happ_invoice_details(
                     id,
                     amount,
                     status,
+                    is_overdue: completed_date.is_none() && invoice_due_date < now,
                     r#type: transaction_type,
                     direction,
                     created_date,
@@ -284,47 +292,48 @@ impl InvoiceSet {
 #[serde(crate = "rocket::serde")]
 #[serde(rename_all = "camelCase")]
 pub struct TransactionAndInvoiceDetails {
-    id: EntryHashB64,
-    amount: String,
-    status: TransactionStatus,
-    r#type: TransactionType,
-    direction: TransactionDirection,
-    created_date: Timestamp,
-    completed_date: Option<Timestamp>,
-    expiration_date: Option<Timestamp>,
-    counterparty: AgentPubKeyB64,
-    note: String,
-    proof_of_service: Option<POS>,
-    url: Option<String>,
-    happ: HappNameAndId,
-    invoice_details: InvoiceDetails,
+    pub id: EntryHashB64,
+    pub amount: String,
+    pub status: TransactionStatus,
+    pub is_overdue: bool, // not paid yet and past its due date
+    pub r#type: TransactionType,
+    pub direction: TransactionDirection,
+    pub created_date: Timestamp,
+    pub completed_date: Option<Timestamp>,
+    pub expiration_date: Option<Timestamp>,
+    pub counterparty: AgentPubKeyB64,
+    pub note: String,
+    pub proof_of_service: Option<POS>,
+    pub url: Option<String>,
+    pub happ: HappNameAndId,
+    pub invoice_details: InvoiceDetails,
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 #[serde(rename_all = "camelCase")]
 pub struct HappNameAndId {
-    name: String,
-    id: ActionHashB64,
+    pub name: String,
+    pub id: ActionHashB64,
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 #[serde(rename_all = "camelCase")]
 pub struct InvoiceDetails {
-    start: Timestamp,
-    end: Timestamp,
-    due: Timestamp,
-    bandwidth: QuantityAndPrice,
-    compute: QuantityAndPrice,
-    storage: QuantityAndPrice,
+    pub start: Timestamp,
+    pub end: Timestamp,
+    pub due: Timestamp,
+    pub bandwidth: QuantityAndPrice,
+    pub compute: QuantityAndPrice,
+    pub storage: QuantityAndPrice,
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct QuantityAndPrice {
-    quantity: u64,
-    price: Fuel,
+    pub quantity: u64,
+    pub price: Fuel,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -141,6 +141,61 @@ async fn install_components() {
     debug!("body: {:#?}", response_body);
     assert!(response_body.contains(&format!("{}", &test_hosted_happ_id)));
 
+    // bulk enable all free happs and test_hosted_happ_id
+    let path = "/apps/hosted/bulk";
+    info!("calling {}", &path);
+    let bulk_payload = serde_json::json!({
+        "action": "enable",
+        "happIds": [test_hosted_happ_id.to_string()],
+        "filter": { "hostingPlan": "free" }
+    });
+    let response = client
+        .post(path)
+        .body(bulk_payload.to_string())
+        .header(ContentType::JSON)
+        .dispatch()
+        .await;
+    debug!("status: {}", response.status());
+    assert_eq!(response.status(), Status::Ok);
+    let response_body = response.into_string().await.unwrap();
+    debug!("body: {:#?}", response_body);
+    assert!(response_body.contains(&format!(
+        "{{\"id\":\"{}\",\"success\":true",
+        &test_hosted_happ_id
+    )));
+
+    // get hourly usage of test_hosted_happ_id
+    let path = format!(
+        "/apps/hosted/{}/usage/series?bucket=hour",
+        &test_hosted_happ_id
+    );
+    info!("calling {}", &path);
+    let response = client.get(path).dispatch().await;
+    debug!("status: {}", response.status());
+    assert_eq!(response.status(), Status::Ok);
+    debug!("body: {:#?}", response.into_string().await);
+
+    // get hosting preferences of test_hosted_happ_id
+    let path = format!("/apps/hosted/{}/preferences", &test_hosted_happ_id);
+    info!("calling {}", &path);
+    let response = client.get(path).dispatch().await;
+    debug!("status: {}", response.status());
+    assert_eq!(response.status(), Status::Ok);
+    let response_body = response.into_string().await.unwrap();
+    debug!("body: {:#?}", response_body);
+    assert!(response_body.contains("hostDefault"));
+
+    // get weekly earnings of test_hosted_happ_id
+    let path = format!(
+        "/apps/hosted/{}/earnings?from=2024-01-01&bucket=week",
+        &test_hosted_happ_id
+    );
+    info!("calling {}", &path);
+    let response = client.get(path).dispatch().await;
+    debug!("status: {}", response.status());
+    assert_eq!(response.status(), Status::Ok);
+    debug!("body: {:#?}", response.into_string().await);
+
     // get service logs for happ
     let path = format!("/apps/hosted/{}/logs", &test_hosted_happ_id);
     info!("calling {}", &path);
@@ -149,6 +204,127 @@ async fn install_components() {
     assert_eq!(response.status(), Status::Ok);
     let response_body = response.into_string().await.unwrap();
     debug!("body: {:#?}", response_body);
+    let logs: Value = serde_json::from_str(&response_body).unwrap();
+    assert_eq!(logs.as_array().unwrap().len(), 9);
+
+    // get service logs for happ page by page
+    let path = format!(
+        "/apps/hosted/{}/logs?entry_type=ActivityLog&status_code=200&limit=5",
+        &test_hosted_happ_id
+    );
+    info!("calling {}", &path);
+    let response = client.get(path).dispatch().await;
+    debug!("status: {}", response.status());
+    assert_eq!(response.status(), Status::Ok);
+    let logs_page: Value = serde_json::from_str(&response.into_string().await.unwrap()).unwrap();
+    assert_eq!(logs_page["entries"].as_array().unwrap().len(), 5);
+    let path = format!(
+        "/apps/hosted/{}/logs?entry_type=ActivityLog&status_code=200&limit=5&cursor={}",
+        &test_hosted_happ_id, logs_page["nextCursor"]
+    );
+    info!("calling {}", &path);
+    let response = client.get(path).dispatch().await;
+    debug!("status: {}", response.status());
+    assert_eq!(response.status(), Status::Ok);
+    let logs_page: Value = serde_json::from_str(&response.into_string().await.unwrap()).unwrap();
+    assert_eq!(logs_page["entries"].as_array().unwrap().len(), 4);
+    assert!(logs_page["nextCursor"].is_null());
+
+    // export service logs for happ in csv
+    let path = format!(
+        "/apps/hosted/{}/logs/export?format=csv",
+        &test_hosted_happ_id
+    );
+    info!("calling {}", &path);
+    let response = client.get(path).dispatch().await;
+    debug!("status: {}", response.status());
+    assert_eq!(response.status(), Status::Ok);
+    let response_body = response.into_string().await.unwrap();
+    debug!("body: {:#?}", response_body);
+    assert_eq!(response_body.lines().count(), 10); // header + 9 entries
+
+    // get disk usage of happ
+    let path = format!("/apps/hosted/{}/storage", &test_hosted_happ_id);
+    info!("calling {}", &path);
+    let response = client.get(path).dispatch().await;
+    debug!("status: {}", response.status());
+    assert_eq!(response.status(), Status::Ok);
+    debug!("body: {:#?}", response.into_string().await);
+
+    // list happs published from this holoport
+    let path = "/apps/published";
+    info!("calling {}", &path);
+    let response = client.get(path).dispatch().await;
+    debug!("status: {}", response.status());
+    assert_eq!(response.status(), Status::Ok);
+    let response_body = response.into_string().await.unwrap();
+    debug!("body: {:#?}", response_body);
+
+    // check drift between hha and holochain
+    let path = "/apps/reconcile?refresh=true";
+    info!("calling {}", &path);
+    let response = client.get(path).dispatch().await;
+    debug!("status: {}", response.status());
+    assert_eq!(response.status(), Status::Ok);
+    let response_body = response.into_string().await.unwrap();
+    debug!("body: {:#?}", response_body);
+    assert!(response_body.contains("\"drifts\""));
+
+    // list apps installed in holochain
+    let path = "/apps/installed?status=running";
+    info!("calling {}", &path);
+    let response = client.get(path).dispatch().await;
+    debug!("status: {}", response.status());
+    assert_eq!(response.status(), Status::Ok);
+    let response_body = response.into_string().await.unwrap();
+    debug!("body: {:#?}", response_body);
+    assert!(response_body.contains(&format!(
+        "\"kind\":\"servicelogger\",\"hostedHappId\":\"{}\"",
+        &test_hosted_happ_id
+    )));
+
+    // list auto-accept decisions
+    let path = "/apps/hosted/auto_accept/log";
+    info!("calling {}", &path);
+    let response = client.get(path).dispatch().await;
+    debug!("status: {}", response.status());
+    assert_eq!(response.status(), Status::Ok);
+
+    // get visibility rules, special happs are hidden by default
+    let path = "/apps/hosted/visibility";
+    info!("calling {}", &path);
+    let response = client.get(path).dispatch().await;
+    debug!("status: {}", response.status());
+    assert_eq!(response.status(), Status::Ok);
+    let response_body = response.into_string().await.unwrap();
+    debug!("body: {:#?}", response_body);
+    assert!(response_body.contains("\"by\":\"specialInstalledAppId\""));
+
+    // run preflight checks of hosting happ
+    let path = format!("/apps/hosted/{}/preflight", &test_hosted_happ_id);
+    info!("calling {}", &path);
+    let response = client.get(path).dispatch().await;
+    debug!("status: {}", response.status());
+    assert_eq!(response.status(), Status::Ok);
+    let response_body = response.into_string().await.unwrap();
+    debug!("body: {:#?}", response_body);
+    assert!(response_body.contains("\"name\":\"servicelogger\",\"status\":\"pass\""));
+
+    // list installed happs that run outdated DNAs
+    let path = "/apps/hosted/outdated";
+    info!("calling {}", &path);
+    let response = client.get(path).dispatch().await;
+    debug!("status: {}", response.status());
+    assert_eq!(response.status(), Status::Ok);
+    debug!("body: {:#?}", response.into_string().await);
+
+    // list web agents hosted for happ
+    let path = format!("/apps/hosted/{}/agents", &test_hosted_happ_id);
+    info!("calling {}", &path);
+    let response = client.get(path).dispatch().await;
+    debug!("status: {}", response.status());
+    assert_eq!(response.status(), Status::Ok);
+    debug!("body: {:#?}", response.into_string().await);
 
     // get holofuel transaction history for 1 week
     let path = format!("/host/redeemable_histogram");
@@ -290,7 +466,43 @@ async fn install_components() {
     assert_eq!(response.status(), Status::Ok);
     let response_body = response.into_string().await.unwrap();
     debug!("body: {:#?}", response_body);
-    assert!(response_body.contains(&format!("{}", &second_test_hosted_happ_id)));
+    let install_job: Value = serde_json::from_str(&response_body).unwrap();
+    assert_eq!(
+        install_job["happId"],
+        second_test_hosted_happ_id.to_string()
+    );
+
+    // Wait for install job to finish
+    let path = format!("/apps/hosted/install/{}", install_job["id"]);
+    let install_job = tokio::time::timeout(std::time::Duration::from_secs(300), async {
+        loop {
+            info!("calling {}", &path);
+            let response = client.get(path.clone()).dispatch().await;
+            debug!("status: {}", response.status());
+            assert_eq!(response.status(), Status::Ok);
+            let install_job: Value =
+                serde_json::from_str(&response.into_string().await.unwrap()).unwrap();
+            if install_job["state"] != "running" {
+                break install_job;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        }
+    })
+    .await
+    .expect("install job did not finish within 5 minutes");
+    debug!("install job: {:#?}", install_job);
+    assert_eq!(install_job["state"], "completed");
+    assert_eq!(install_job["progress"], 100);
+
+    // Bundle of second hosted happ is kept in bundle cache
+    let path = "/apps/bundles";
+    info!("calling {}", &path);
+    let response = client.get(path).dispatch().await;
+    debug!("status: {}", response.status());
+    assert_eq!(response.status(), Status::Ok);
+    let response_body = response.into_string().await.unwrap();
+    debug!("body: {:#?}", response_body);
+    assert!(response_body.contains(HHA_URL));
 
     // Test ability to call the second hosted happ:
     // Open ws connection to servicelogger instance for hosted happ
@@ -319,7 +531,7 @@ async fn install_components() {
             .unwrap()
             .into();
     let register_payload = HappInput {
-        hosted_urls: vec!["test_happ_3_host_url".to_string()],
+        hosted_urls: vec!["test-happ-3.holo.host".to_string()],
         bundle_url: HHA_URL.to_string(),
         special_installed_app_id: None,
         name: "Test Happ 3".to_string(),
@@ -352,6 +564,19 @@ async fn install_components() {
     struct Bundle {
         id: String,
     }
+
+    // registration with invalid fields is rejected before reaching hha
+    let mut invalid_payload = register_payload.clone();
+    invalid_payload.hosted_urls = vec!["test_happ_3_host_url".to_string()];
+    invalid_payload.jurisdictions = vec!["Narnia".to_string()];
+    let response = client
+        .post("/apps/hosted/register")
+        .body(serde_json::to_string(&invalid_payload).unwrap())
+        .header(ContentType::JSON)
+        .dispatch()
+        .await;
+    debug!("status: {}", response.status());
+    assert_eq!(response.status(), Status::BadRequest);
     let third_test_hosted_happ = serde_json::from_str::<Bundle>(&response_body).unwrap();
     debug!("third_test_hosted_happ: {:#?}", third_test_hosted_happ);
 
@@ -383,4 +608,30 @@ async fn install_components() {
     debug!("body: {:#?}", response_body);
     // matches the contents of './servicelogger_prefs'
     assert!(response_body.contains("\"max_fuel_before_invoice\":\"1\",\"price_compute\":\"0\",\"price_storage\":\"0\",\"price_bandwidth\":\"0\",\"max_time_before_invoice\":{\"secs\":18446744073709551615,\"nanos\":999999999},\"invoice_due_in_days\":7,\"jurisdiction_prefs\":null,\"categories_prefs\":null}"));
+
+    // uninstall second hosted happ in dry-run mode
+    let path = format!(
+        "/apps/hosted/{}?keep_data=true",
+        &second_test_hosted_happ_id
+    );
+    info!("calling DELETE {}", &path);
+    let response = client.delete(path).dispatch().await;
+    debug!("status: {}", response.status());
+    assert_eq!(response.status(), Status::Ok);
+    let response_body = response.into_string().await.unwrap();
+    debug!("body: {:#?}", response_body);
+    assert!(response_body.contains(&format!("{}::servicelogger", &second_test_hosted_happ_id)));
+    assert!(response_body.contains("\"dryRun\":true"));
+
+    // uninstall second hosted happ
+    let path = format!("/apps/hosted/{}", &second_test_hosted_happ_id);
+    info!("calling DELETE {}", &path);
+    let response = client.delete(path).dispatch().await;
+    debug!("status: {}", response.status());
+    assert_eq!(response.status(), Status::Ok);
+    debug!("body: {:#?}", response.into_string().await);
+    let installed_apps = test.admin_ws.list_apps(None).await.unwrap();
+    assert!(!installed_apps.iter().any(|app| app
+        .installed_app_id
+        .contains(&second_test_hosted_happ_id.to_string())));
 }